@@ -5,13 +5,27 @@ use serde_json::{Map, Value};
 use tracing::{debug, error, info, warn};
 
 use crate::{
-    content::{Content, Contents},
+    content::{Content, ContentKind, ContentPage, Contents, DirectorySummary, PageRequest},
     errors::Error,
-    events::{self, buffer, Events},
-    messages::Messages,
-    query::Query,
+    events::{self, buffer, function, Events},
+    inspect::ParquetInspection,
+    messages::{buffer::Materialize, Messages},
+    profile::{BufferProfile, ProfileEvent, ProfileRequest, PROFILE_EVENT},
+    query::{
+        diff::{DiffRequest, RowDiff},
+        explain::Explanation,
+        functions::FunctionDefinition,
+        naming::BufferTables,
+        results::IpcResult,
+        schemas::SchemaComparison,
+        summary::ResultSummary,
+        Query,
+    },
+    search::{SearchEvent, SearchRequest, SEARCH_EVENT},
     state::{
-        store::{get_home_dir, Metadata},
+        runtime::RuntimeSettings,
+        settings::Settings,
+        store::{get_home_dir, Connection, ConnectionTest, Metadata},
         table::Table,
         App, BufferState,
     },
@@ -35,6 +49,7 @@ pub async fn contents(
     app: tauri::State<'_, Arc<App>>,
     id: usize,
     prefix: String,
+    summarize: Option<bool>,
 ) -> Result<Contents, Error> {
     let store = app
         .get_store(&id)
@@ -58,55 +73,232 @@ pub async fn contents(
     };
     let path = Path::parse(&prefix)?;
 
-    let list = store.client.list_with_delimiter(Some(&path)).await;
+    let contents = Contents::load(store.client.as_ref(), &prefix).await;
 
-    let list = match list {
-        Ok(list) => list,
+    let mut contents = match contents {
+        Ok(contents) => contents,
         Err(e) => {
             error!(?e, path=?path, "Failed to list contents");
-            return Err(Error::ObjectStore(e));
+            return Err(e);
         }
     };
 
-    let files: Vec<Content> = list
-        .objects
-        .iter()
-        .map(|file| Content::new(file.location.to_string(), false))
-        .collect();
-    let dirs: Vec<Content> = list
-        .common_prefixes
-        .iter()
-        .map(|dir| Content::new(dir.to_string(), true))
-        .collect();
-
-    let mut items = [files, dirs].concat();
-    items.sort_by(|a, b| a.prefix.cmp(&b.prefix));
-
-    if items.len() == 0 {
-        debug!("no items found at this path, checking if it is a file");
-        let object = store.client.get(&path).await;
+    if summarize.unwrap_or(false) {
+        for item in contents.items.iter_mut() {
+            if !matches!(item.kind, ContentKind::Directory) {
+                continue;
+            }
 
-        let object = match object {
-            Ok(object) => object,
+            let dir = Path::parse(&item.prefix)?;
+            match DirectorySummary::load(store.client.as_ref(), &dir).await {
+                Ok(summary) => *item = item.clone().with_summary(summary),
+                Err(e) => {
+                    warn!(?e, path = %dir, "Failed to summarize directory");
+                }
+            }
+        }
+    }
+
+    if contents.items.len() == 0 {
+        debug!("no items found at this path, checking if it is a file");
+        match store.client.head(&path).await {
+            Ok(meta) => contents.items.push(Content::from_meta(&meta)),
+            Err(object_store::Error::NotFound { .. }) => {
+                debug!(path = %path, "no file at this path, the prefix is empty");
+            }
             Err(e) => {
-                error!(path=%path, "failed to get item at path");
+                error!(?e, path = %path, "failed to get item at path");
                 return Err(Error::ObjectStore(e));
             }
-        };
+        }
+    }
+
+    return Ok(contents);
+}
 
-        todo!(); // Support displaying file contents
-                 // let meta = object.meta;
-                 // let schema = app.infer_schema(&store.client, &[meta]).await;
-                 // debug!("Found Schema {:?}", schema);
-                 // dbg!(schema);
+#[tauri::command]
+#[tracing::instrument(
+    name="Command: inspect parquet file",
+    skip(app),
+    fields(
+        request=%Uuid::new_v4()
+    )
+)]
+pub async fn inspect_parquet(
+    app: tauri::State<'_, Arc<App>>,
+    id: usize,
+    path: String,
+) -> Result<ParquetInspection, Error> {
+    let store = app
+        .get_store(&id)
+        .await
+        .ok_or(Error::NotFound(format!("object store with id {}", id)))?;
+    let path = Path::parse(&path)?;
+
+    let inspection = ParquetInspection::load(&store, &path).await;
+
+    if let Err(e) = &inspection {
+        error!(?e, path = %path, "Failed to inspect parquet file");
     }
 
-    let contents = Contents {
-        prefix: prefix.clone(),
-        items,
+    inspection
+}
+
+#[tauri::command]
+#[tracing::instrument(
+    name="Command: list contents page",
+    skip(app),
+    fields(
+        request=%Uuid::new_v4()
+    )
+)]
+pub async fn list_contents(
+    app: tauri::State<'_, Arc<App>>,
+    id: usize,
+    prefix: String,
+    page: PageRequest,
+) -> Result<ContentPage, Error> {
+    let page = app.list_contents(&id, prefix, &page).await;
+
+    if let Err(e) = &page {
+        error!(?e, store = id, "Failed to list contents page");
+    }
+
+    page
+}
+
+#[tauri::command]
+#[tracing::instrument(
+    name="Command: search",
+    skip(app, window),
+    fields(
+        request=%Uuid::new_v4()
+    )
+)]
+pub async fn search(
+    app: tauri::State<'_, Arc<App>>,
+    window: tauri::Window,
+    id: usize,
+    search: SearchRequest,
+) -> Result<usize, Error> {
+    let store = app
+        .get_store(&id)
+        .await
+        .ok_or(Error::NotFound(format!("object store with id {}", id)))?;
+
+    let search = app.start_search(search).await;
+    let search = match search {
+        Ok(search) => search,
+        Err(e) => {
+            warn!(?e, "Failed to start search");
+            return Err(e);
+        }
     };
+    let search_id = search.id();
 
-    return Ok(contents);
+    let app = app.inner().clone();
+    tokio::spawn(async move {
+        let emit = |event: SearchEvent| {
+            if let Err(e) = window.emit(SEARCH_EVENT, event) {
+                error!(?e, search = search_id, "Failed to emit search event");
+            }
+        };
+
+        let result = search.run(&store, &emit).await;
+        emit(result);
+        app.finish_search(search_id).await;
+    });
+
+    Ok(search_id)
+}
+
+#[tauri::command]
+#[tracing::instrument(
+    name="Command: profile buffer",
+    skip(app, window),
+    fields(
+        request=%Uuid::new_v4()
+    )
+)]
+pub async fn profile_buffer(
+    app: tauri::State<'_, Arc<App>>,
+    window: tauri::Window,
+    request: ProfileRequest,
+) -> Result<usize, Error> {
+    let job = app.start_profile(request).await;
+    let job = match job {
+        Ok(job) => job,
+        Err(e) => {
+            warn!(?e, "Failed to start profile");
+            return Err(e);
+        }
+    };
+    let profile_id = job.id();
+
+    let app = app.inner().clone();
+    tokio::spawn(async move {
+        let emit = |event: ProfileEvent| {
+            if let Err(e) = window.emit(PROFILE_EVENT, event) {
+                error!(?e, profile = profile_id, "Failed to emit profile event");
+            }
+        };
+
+        let result = app.run_profile(&job, &emit).await;
+        emit(result);
+    });
+
+    Ok(profile_id)
+}
+
+#[tauri::command]
+#[tracing::instrument(
+    name="Command: cancel profile",
+    skip(app),
+    fields(
+        request=%Uuid::new_v4()
+    )
+)]
+pub async fn cancel_profile(app: tauri::State<'_, Arc<App>>, id: usize) -> Result<(), Error> {
+    let result = app.cancel_profile(&id).await;
+
+    if let Err(e) = &result {
+        warn!(?e, profile = id, "Failed to cancel profile");
+    }
+
+    result
+}
+
+#[tauri::command]
+#[tracing::instrument(
+    name="Command: get profile",
+    skip(app),
+    fields(
+        request=%Uuid::new_v4()
+    )
+)]
+pub async fn get_profile(
+    app: tauri::State<'_, Arc<App>>,
+    buffer: usize,
+) -> Result<Option<BufferProfile>, Error> {
+    Ok(app.get_profile(&buffer).await)
+}
+
+#[tauri::command]
+#[tracing::instrument(
+    name="Command: cancel search",
+    skip(app),
+    fields(
+        request=%Uuid::new_v4()
+    )
+)]
+pub async fn cancel_search(app: tauri::State<'_, Arc<App>>, id: usize) -> Result<(), Error> {
+    let result = app.cancel_search(&id).await;
+
+    if let Err(e) = &result {
+        warn!(?e, search = id, "Failed to cancel search");
+    }
+
+    result
 }
 
 #[tauri::command]
@@ -135,6 +327,48 @@ pub async fn update(app: tauri::State<'_, Arc<App>>, message: Messages) -> Resul
             });
             event
         }
+        Messages::CreateView(message) => {
+            let event_id = app.next_event_id().await;
+            let event = Events::CreateView(buffer::CreateView {
+                id: event_id,
+                name: message.name,
+                statement: message.statement,
+            });
+            event
+        }
+        Messages::CreateFunction(definition) => {
+            let event_id = app.next_event_id().await;
+            let event = Events::CreateFunction(function::Create {
+                id: event_id,
+                definition,
+            });
+            event
+        }
+        Messages::UpdateBufferSchema(message) => {
+            let event_id = app.next_event_id().await;
+            let event = Events::UpdateBufferSchema(buffer::UpdateSchema {
+                id: event_id,
+                buffer: message.buffer,
+                schema_override: message.schema_override,
+            });
+            event
+        }
+        Messages::PinBufferVersions(message) => {
+            if message.as_of.is_some() {
+                if let Err(e) = app.check_versioned(&message.buffer).await {
+                    error!(?e, buffer = message.buffer, "failed to pin buffer versions");
+                    return Err(e);
+                }
+            }
+
+            let event_id = app.next_event_id().await;
+            let event = Events::PinBufferVersions(buffer::PinVersions {
+                id: event_id,
+                buffer: message.buffer,
+                as_of: message.as_of,
+            });
+            event
+        }
     };
     let result = app.save(&event).await;
 
@@ -162,6 +396,24 @@ pub async fn storages(app: tauri::State<'_, Arc<App>>) -> Result<Vec<Metadata>,
     Ok(metadata)
 }
 
+#[tauri::command]
+#[tracing::instrument(
+    name="Command: test connection",
+    skip(connection),
+    fields(
+        request=%Uuid::new_v4()
+    )
+)]
+pub async fn test_connection(
+    connection: Connection,
+    prefix: Option<String>,
+) -> Result<ConnectionTest, Error> {
+    let prefix = prefix.unwrap_or_default();
+    let result = ConnectionTest::run(&connection, &prefix).await;
+
+    Ok(result)
+}
+
 #[tauri::command]
 #[tracing::instrument(
     name="Command: get storage",
@@ -232,6 +484,138 @@ pub async fn query(
     }
 }
 
+#[tauri::command]
+#[tracing::instrument(
+    name="Command: execute query as arrow ipc",
+    skip(app),
+    fields(
+        request=%Uuid::new_v4()
+    )
+)]
+pub async fn query_ipc(app: tauri::State<'_, Arc<App>>, query: Query) -> Result<IpcResult, Error> {
+    let result = app.query_ipc(&query).await;
+
+    match result {
+        Ok(result) => {
+            info!(num_rows = result.num_rows, "Completed query");
+            Ok(result)
+        }
+        Err(e) => {
+            error!(?e, "Failed to complete query");
+            Err(e)
+        }
+    }
+}
+
+#[tauri::command]
+#[tracing::instrument(
+    name="Command: summarize query",
+    skip(app),
+    fields(
+        request=%Uuid::new_v4()
+    )
+)]
+pub async fn summarize(
+    app: tauri::State<'_, Arc<App>>,
+    query: Query,
+    top_k: Option<usize>,
+) -> Result<ResultSummary, Error> {
+    let result = app.summarize(&query, top_k).await;
+
+    match result {
+        Ok(summary) => {
+            info!(num_rows = summary.num_rows, "Summarized query");
+            Ok(summary)
+        }
+        Err(e) => {
+            error!(?e, "Failed to summarize query");
+            Err(e)
+        }
+    }
+}
+
+#[tauri::command]
+#[tracing::instrument(
+    name="Command: diff rows",
+    skip(app),
+    fields(
+        request=%Uuid::new_v4()
+    )
+)]
+pub async fn diff(app: tauri::State<'_, Arc<App>>, request: DiffRequest) -> Result<RowDiff, Error> {
+    let result = app.diff(request).await;
+
+    match result {
+        Ok(diff) => {
+            info!(
+                added = diff.num_added,
+                removed = diff.num_removed,
+                changed = diff.num_changed,
+                "Diffed rows"
+            );
+            Ok(diff)
+        }
+        Err(e) => {
+            error!(?e, "Failed to diff rows");
+            Err(e)
+        }
+    }
+}
+
+#[tauri::command]
+#[tracing::instrument(
+    name="Command: explain query",
+    skip(app),
+    fields(
+        request=%Uuid::new_v4()
+    )
+)]
+pub async fn explain(
+    app: tauri::State<'_, Arc<App>>,
+    query: Query,
+    analyze: bool,
+) -> Result<Explanation, Error> {
+    let result = app.explain(&query, analyze).await;
+
+    match result {
+        Ok(explanation) => {
+            info!("Explained query");
+            Ok(explanation)
+        }
+        Err(e) => {
+            error!(?e, "Failed to explain query");
+            Err(e)
+        }
+    }
+}
+
+#[tauri::command]
+#[tracing::instrument(
+    name="Command: materialize query",
+    skip(app),
+    fields(
+        request=%Uuid::new_v4()
+    )
+)]
+pub async fn materialize(
+    app: tauri::State<'_, Arc<App>>,
+    query: Query,
+    target: Materialize,
+) -> Result<(), Error> {
+    let result = app.materialize(&query, target).await;
+
+    match result {
+        Ok(()) => {
+            info!("Materialized query");
+            Ok(())
+        }
+        Err(e) => {
+            error!(?e, "Failed to materialize query");
+            Err(e)
+        }
+    }
+}
+
 #[tauri::command]
 #[tracing::instrument(
     name="Command: get buffers",
@@ -265,3 +649,156 @@ pub async fn get_table(app: tauri::State<'_, Arc<App>>, id: usize) -> Result<Tab
 
     table
 }
+
+#[tauri::command]
+#[tracing::instrument(
+    name="Command: compare schemas",
+    skip(app),
+    fields(
+        request=%Uuid::new_v4()
+    )
+)]
+pub async fn compare_schemas(
+    app: tauri::State<'_, Arc<App>>,
+    id: usize,
+    sample: Option<usize>,
+) -> Result<SchemaComparison, Error> {
+    let comparison = app.compare_schemas(&id, sample).await;
+
+    if let Err(e) = &comparison {
+        error!(?e, buffer = id, "failed to compare schemas");
+    }
+
+    comparison
+}
+
+#[tauri::command]
+#[tracing::instrument(
+    name="Command: refresh schema",
+    skip(app),
+    fields(
+        request=%Uuid::new_v4()
+    )
+)]
+pub async fn refresh_schema(app: tauri::State<'_, Arc<App>>, id: usize) -> Result<Table, Error> {
+    let table = app.refresh_schema(&id).await;
+
+    if let Err(e) = &table {
+        error!(?e, table = id, "failed to refresh schema");
+    }
+
+    table
+}
+
+#[tauri::command]
+#[tracing::instrument(
+    name="Command: get buffer tables",
+    skip(app),
+    fields(
+        request=%Uuid::new_v4()
+    )
+)]
+pub async fn get_buffer_tables(
+    app: tauri::State<'_, Arc<App>>,
+    id: usize,
+) -> Result<BufferTables, Error> {
+    let tables = app.get_buffer_tables(&id).await;
+
+    if let Err(e) = &tables {
+        error!(?e, buffer = id, "failed to get buffer tables");
+    }
+
+    tables
+}
+
+#[tauri::command]
+#[tracing::instrument(
+    name="Command: get runtime settings",
+    skip(app),
+    fields(
+        request=%Uuid::new_v4()
+    )
+)]
+pub async fn get_runtime_settings(
+    app: tauri::State<'_, Arc<App>>,
+) -> Result<RuntimeSettings, Error> {
+    Ok(app.get_runtime_settings().await)
+}
+
+#[tauri::command]
+#[tracing::instrument(
+    name="Command: update runtime settings",
+    skip(app),
+    fields(
+        request=%Uuid::new_v4()
+    )
+)]
+pub async fn update_runtime_settings(
+    app: tauri::State<'_, Arc<App>>,
+    settings: RuntimeSettings,
+) -> Result<(), Error> {
+    let result = app.update_runtime_settings(settings).await;
+
+    match result {
+        Ok(()) => {
+            info!("Updated runtime settings");
+            Ok(())
+        }
+        Err(e) => {
+            error!(?e, "Failed to update runtime settings");
+            Err(e)
+        }
+    }
+}
+
+#[tauri::command]
+#[tracing::instrument(
+    name="Command: get settings",
+    skip(app),
+    fields(
+        request=%Uuid::new_v4()
+    )
+)]
+pub async fn get_settings(app: tauri::State<'_, Arc<App>>) -> Result<Settings, Error> {
+    Ok(app.get_settings().await)
+}
+
+#[tauri::command]
+#[tracing::instrument(
+    name="Command: update settings",
+    skip(app),
+    fields(
+        request=%Uuid::new_v4()
+    )
+)]
+pub async fn update_settings(
+    app: tauri::State<'_, Arc<App>>,
+    settings: Settings,
+) -> Result<(), Error> {
+    let result = app.update_settings(settings).await;
+
+    match result {
+        Ok(()) => {
+            info!("Updated settings");
+            Ok(())
+        }
+        Err(e) => {
+            error!(?e, "Failed to update settings");
+            Err(e)
+        }
+    }
+}
+
+#[tauri::command]
+#[tracing::instrument(
+    name="Command: get functions",
+    skip(app),
+    fields(
+        request=%Uuid::new_v4()
+    )
+)]
+pub async fn get_functions(
+    app: tauri::State<'_, Arc<App>>,
+) -> Result<Vec<FunctionDefinition>, Error> {
+    Ok(app.list_functions().await)
+}
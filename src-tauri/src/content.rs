@@ -1,26 +1,144 @@
+use std::cmp::Ordering;
+
+use aws_sdk_s3::{error::DisplayErrorContext, operation::list_objects_v2::ListObjectsV2Output};
+use futures::TryStreamExt;
+use object_store::{path::Path, ObjectMeta, ObjectStore as ObjectStoreClient};
+use tokio::sync::mpsc;
+
+use crate::{
+    errors::Error,
+    state::store::{Connection, ObjectStore},
+};
+
+/// the number of store pages listed ahead of the page being read
+const LISTING_PAGES_AHEAD: usize = 4;
+
+const DELIMITER: &str = "/";
+
 #[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy)]
 pub enum ContentKind {
     Directory,
     File,
 }
 
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContentFormat {
+    Parquet,
+    Csv,
+    Json,
+    Avro,
+    Arrow,
+    Unknown,
+}
+
+impl ContentFormat {
+    pub fn detect(location: &str) -> Self {
+        let location = location.to_lowercase();
+        // strip compression extensions so `file.csv.gz` is detected as csv
+        let location = [".gz", ".bz2", ".xz", ".zst"]
+            .iter()
+            .find_map(|ext| location.strip_suffix(ext))
+            .unwrap_or(&location);
+
+        match location.rsplit_once('.').map(|(_, ext)| ext) {
+            Some("parquet") => Self::Parquet,
+            Some("csv") | Some("tsv") => Self::Csv,
+            Some("json") | Some("ndjson") | Some("jsonl") => Self::Json,
+            Some("avro") => Self::Avro,
+            Some("arrow") | Some("ipc") | Some("feather") => Self::Arrow,
+            _ => Self::Unknown,
+        }
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default)]
+pub struct DirectorySummary {
+    pub num_files: usize,
+    pub size: usize,
+    pub last_modified: Option<i64>,
+}
+
+impl DirectorySummary {
+    pub async fn load(client: &dyn ObjectStoreClient, prefix: &Path) -> Result<Self, Error> {
+        let summary = client
+            .list(Some(prefix))
+            .try_fold(Self::default(), |mut summary, meta| async move {
+                summary.num_files += 1;
+                summary.size += meta.size;
+                let last_modified = meta.last_modified.timestamp_millis();
+                summary.last_modified = summary.last_modified.max(Some(last_modified));
+                Ok(summary)
+            })
+            .await?;
+
+        Ok(summary)
+    }
+}
+
 #[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
 pub struct Content {
     pub prefix: String,
     pub kind: ContentKind,
+    pub size: Option<usize>,
+    /// milliseconds since the unix epoch
+    pub last_modified: Option<i64>,
+    pub e_tag: Option<String>,
+    pub version: Option<String>,
+    pub format: Option<ContentFormat>,
+    pub summary: Option<DirectorySummary>,
 }
 
 impl Content {
     pub fn new(prefix: String, is_dir: bool) -> Self {
-        if is_dir {
-            return Self {
-                prefix,
-                kind: ContentKind::Directory,
-            };
-        }
+        let kind = if is_dir {
+            ContentKind::Directory
+        } else {
+            ContentKind::File
+        };
+
         Self {
             prefix,
-            kind: ContentKind::File,
+            kind,
+            size: None,
+            last_modified: None,
+            e_tag: None,
+            version: None,
+            format: None,
+            summary: None,
+        }
+    }
+
+    /// directories are ordered as if they ended in `/`, the order object stores list keys in,
+    /// so `a/b.txt` comes before the directory `a/b`
+    pub fn cmp_name(&self, other: &Self) -> Ordering {
+        let name = |item: &Self| match item.kind {
+            ContentKind::Directory => format!("{}{}", item.prefix, DELIMITER),
+            ContentKind::File => item.prefix.clone(),
+        };
+
+        name(self).cmp(&name(other))
+    }
+
+    pub fn from_meta(meta: &ObjectMeta) -> Self {
+        let prefix = meta.location.to_string();
+        let format = ContentFormat::detect(&prefix);
+
+        Self {
+            size: Some(meta.size),
+            last_modified: Some(meta.last_modified.timestamp_millis()),
+            e_tag: meta.e_tag.clone(),
+            version: meta.version.clone(),
+            format: Some(format),
+            ..Self::new(prefix, false)
+        }
+    }
+
+    pub fn with_summary(self, summary: DirectorySummary) -> Self {
+        Self {
+            last_modified: summary.last_modified,
+            size: Some(summary.size),
+            summary: Some(summary),
+            ..self
         }
     }
 }
@@ -30,3 +148,420 @@ pub struct Contents {
     pub prefix: String,
     pub items: Vec<Content>,
 }
+
+impl Contents {
+    pub async fn load(client: &dyn ObjectStoreClient, prefix: &str) -> Result<Self, Error> {
+        let path = Path::parse(prefix)?;
+        let list = client.list_with_delimiter(Some(&path)).await?;
+
+        let files = list.objects.iter().map(Content::from_meta);
+        let dirs = list
+            .common_prefixes
+            .iter()
+            .map(|dir| Content::new(dir.to_string(), true));
+
+        let mut items: Vec<Content> = files.chain(dirs).collect();
+        items.sort_by(Content::cmp_name);
+
+        Ok(Self {
+            prefix: prefix.to_string(),
+            items,
+        })
+    }
+
+    pub fn filter(&mut self, filter: &str) {
+        let filter = filter.to_lowercase();
+        self.items.retain(|item| Self::matches(item, &filter));
+    }
+
+    /// whether the name of an item contains a lowercase filter
+    fn matches(item: &Content, filter: &str) -> bool {
+        let name = item.prefix.rsplit('/').next().unwrap_or(&item.prefix);
+        name.to_lowercase().contains(filter)
+    }
+
+    pub fn sort(&mut self, sort: ContentSort, descending: bool) {
+        match sort {
+            ContentSort::Name => self.items.sort_by(Content::cmp_name),
+            ContentSort::Size => self.items.sort_by_key(|item| item.size),
+            ContentSort::LastModified => self.items.sort_by_key(|item| item.last_modified),
+        }
+
+        if descending {
+            self.items.reverse();
+        }
+    }
+}
+
+/// the direct children of a prefix read from a delimiter listing of the store as pages are
+/// requested. the listing runs in a task that stops when the listing is dropped
+#[derive(Debug)]
+pub struct Listing {
+    contents: Contents,
+    filter: Option<String>,
+    /// `None` once every page has been read
+    receiver: Option<mpsc::Receiver<Result<Vec<Content>, Error>>>,
+}
+
+impl Listing {
+    pub fn start(store: ObjectStore, prefix: &str, filter: Option<&str>) -> Result<Self, Error> {
+        let path = Path::parse(prefix)?;
+        let (sender, receiver) = mpsc::channel(LISTING_PAGES_AHEAD);
+        tokio::spawn(list_pages(store, path, sender));
+
+        Ok(Self {
+            contents: Contents {
+                prefix: prefix.to_string(),
+                items: Vec::new(),
+            },
+            filter: filter.map(str::to_lowercase),
+            receiver: Some(receiver),
+        })
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.receiver.is_none()
+    }
+
+    /// reads pages from the store until `len` items are listed or the listing is complete. pages
+    /// follow each other in name order, so sorting each page keeps the items sorted by name
+    async fn fill(&mut self, len: usize) -> Result<(), Error> {
+        while self.contents.items.len() < len {
+            let receiver = match self.receiver.as_mut() {
+                Some(receiver) => receiver,
+                None => break,
+            };
+
+            let mut page = match receiver.recv().await {
+                Some(page) => page?,
+                None => {
+                    self.receiver = None;
+                    break;
+                }
+            };
+
+            if let Some(filter) = &self.filter {
+                page.retain(|item| Contents::matches(item, filter));
+            }
+            page.sort_by(Content::cmp_name);
+            self.contents.items.extend(page);
+        }
+
+        Ok(())
+    }
+
+    /// reads every item and sorts them, items are sorted by name otherwise
+    pub async fn sort(&mut self, sort: ContentSort, descending: bool) -> Result<(), Error> {
+        self.fill(usize::MAX).await?;
+        self.contents.sort(sort, descending);
+
+        Ok(())
+    }
+
+    pub async fn page(
+        &mut self,
+        listing: usize,
+        offset: usize,
+        page_size: usize,
+    ) -> Result<ContentPage, Error> {
+        // one item past the page tells whether there is a next page
+        self.fill(offset.saturating_add(page_size).saturating_add(1))
+            .await?;
+
+        Ok(ContentPage::new(
+            listing,
+            &self.contents,
+            offset,
+            page_size,
+            self.is_complete(),
+        ))
+    }
+}
+
+/// the objects and common prefixes of an s3 listing page
+fn s3_page(page: &ListObjectsV2Output) -> Vec<Content> {
+    let files = page.contents().iter().filter_map(|object| {
+        let key = object.key()?;
+        Some(Content {
+            size: object.size().map(|size| size as usize),
+            last_modified: object
+                .last_modified()
+                .and_then(|date| date.to_millis().ok()),
+            e_tag: object.e_tag().map(String::from),
+            format: Some(ContentFormat::detect(key)),
+            ..Content::new(key.to_string(), false)
+        })
+    });
+    let dirs = page.common_prefixes().iter().filter_map(|prefix| {
+        let prefix = prefix.prefix()?.trim_end_matches(DELIMITER);
+        Some(Content::new(prefix.to_string(), true))
+    });
+
+    files.chain(dirs).collect()
+}
+
+/// sends the direct children of a prefix a page at a time. s3 is listed with continuation so a
+/// page only reads one page of keys, other stores list a single directory at once
+async fn list_pages(
+    store: ObjectStore,
+    path: Path,
+    sender: mpsc::Sender<Result<Vec<Content>, Error>>,
+) {
+    let connection = match &store.connection {
+        Connection::Remote(connection) => connection,
+        Connection::Local(_) => {
+            let page = store
+                .client
+                .list_with_delimiter(Some(&path))
+                .await
+                .map(|list| {
+                    let files = list.objects.iter().map(Content::from_meta);
+                    let dirs = list
+                        .common_prefixes
+                        .iter()
+                        .map(|dir| Content::new(dir.to_string(), true));
+                    files.chain(dirs).collect()
+                })
+                .map_err(Error::from);
+            let _ = sender.send(page).await;
+            return;
+        }
+    };
+
+    let client = connection.s3_client();
+    let mut request = client
+        .list_objects_v2()
+        .bucket(&connection.bucket)
+        .delimiter(DELIMITER);
+    if !path.as_ref().is_empty() {
+        request = request.prefix(format!("{}{}", path, DELIMITER));
+    }
+
+    let mut pages = request.into_paginator().send();
+    while let Some(page) = pages.next().await {
+        let page = page.map(|page| s3_page(&page)).map_err(|e| {
+            Error::ObjectStore(object_store::Error::Generic {
+                store: "S3",
+                source: DisplayErrorContext(e).to_string().into(),
+            })
+        });
+        let is_err = page.is_err();
+        if sender.send(page).await.is_err() || is_err {
+            break;
+        }
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, Default)]
+pub enum ContentSort {
+    #[default]
+    Name,
+    Size,
+    LastModified,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default)]
+pub struct PageRequest {
+    pub page_size: Option<usize>,
+    /// continuation token returned with the previous page, sorting and filtering are fixed by the
+    /// request that started the listing
+    pub token: Option<String>,
+    pub sort: Option<ContentSort>,
+    pub descending: Option<bool>,
+    pub filter: Option<String>,
+}
+
+impl PageRequest {
+    pub const DEFAULT_PAGE_SIZE: usize = 500;
+
+    pub fn page_size(&self) -> usize {
+        self.page_size.unwrap_or(Self::DEFAULT_PAGE_SIZE).max(1)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PageToken {
+    pub listing: usize,
+    pub offset: usize,
+}
+
+impl PageToken {
+    pub fn parse(token: &str) -> Result<Self, Error> {
+        let invalid = || Error::InvalidPageToken(token.to_string());
+        let (listing, offset) = token.split_once(':').ok_or_else(invalid)?;
+
+        Ok(Self {
+            listing: listing.parse().map_err(|_| invalid())?,
+            offset: offset.parse().map_err(|_| invalid())?,
+        })
+    }
+}
+
+impl std::fmt::Display for PageToken {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.listing, self.offset)
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct ContentPage {
+    pub prefix: String,
+    pub items: Vec<Content>,
+    /// the number of items once the listing is complete
+    pub total: Option<usize>,
+    pub next_token: Option<String>,
+}
+
+impl ContentPage {
+    pub fn new(
+        listing: usize,
+        contents: &Contents,
+        offset: usize,
+        page_size: usize,
+        is_complete: bool,
+    ) -> Self {
+        let len = contents.items.len();
+        let start = offset.min(len);
+        let end = start.saturating_add(page_size).min(len);
+
+        let next_token = if end < len || !is_complete {
+            Some(
+                PageToken {
+                    listing,
+                    offset: end,
+                }
+                .to_string(),
+            )
+        } else {
+            None
+        };
+
+        Self {
+            prefix: contents.prefix.clone(),
+            items: contents.items[start..end].to_vec(),
+            total: if is_complete { Some(len) } else { None },
+            next_token,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_content_page {
+    use crate::state::store::{LocalConnection, Metadata, ObjectStoreKind};
+
+    use super::*;
+
+    fn contents(num_items: usize) -> Contents {
+        Contents {
+            prefix: String::from("dir1"),
+            items: (0..num_items)
+                .map(|i| Content::new(format!("dir1/file{i}"), false))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn token_round_trips() {
+        let token = PageToken {
+            listing: 3,
+            offset: 500,
+        };
+
+        assert_eq!(PageToken::parse(&token.to_string()).unwrap(), token);
+        assert!(PageToken::parse("3").is_err());
+        assert!(PageToken::parse("a:1").is_err());
+    }
+
+    #[test]
+    fn pages_until_exhausted() {
+        let contents = contents(5);
+
+        let page = ContentPage::new(1, &contents, 0, 2, true);
+        assert_eq!(page.items.len(), 2);
+        assert_eq!(page.total, Some(5));
+        assert_eq!(page.next_token, Some(String::from("1:2")));
+
+        let page = ContentPage::new(1, &contents, 4, 2, true);
+        assert_eq!(page.items.len(), 1);
+        assert_eq!(page.next_token, None);
+
+        let page = ContentPage::new(1, &contents, 10, 2, true);
+        assert_eq!(page.items.len(), 0);
+        assert_eq!(page.next_token, None);
+    }
+
+    #[test]
+    fn continues_incomplete_listing() {
+        let contents = contents(2);
+
+        let page = ContentPage::new(1, &contents, 0, 2, false);
+        assert_eq!(page.items.len(), 2);
+        assert_eq!(page.total, None);
+        assert_eq!(page.next_token, Some(String::from("1:2")));
+    }
+
+    #[test]
+    fn sorts_directories_in_key_order() {
+        let mut contents = Contents {
+            prefix: String::from("a"),
+            items: vec![
+                Content::new(String::from("a/b"), true),
+                Content::new(String::from("a/b.txt"), false),
+                Content::new(String::from("a/a"), false),
+            ],
+        };
+
+        contents.sort(ContentSort::Name, false);
+        let names: Vec<&str> = contents
+            .items
+            .iter()
+            .map(|item| item.prefix.as_str())
+            .collect();
+
+        assert_eq!(names, vec!["a/a", "a/b.txt", "a/b"]);
+    }
+
+    #[tokio::test]
+    async fn lists_direct_children_of_local_dir() {
+        let dir = std::env::temp_dir().join(format!("file-fusion-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(dir.join("b/c")).unwrap();
+        for file in ["a.txt", "b.txt", "b/c/d.txt"] {
+            std::fs::write(dir.join(file), "x").unwrap();
+        }
+        let store = ObjectStore::new(
+            Metadata {
+                id: 0,
+                name: String::from("Local"),
+                prefix: String::new(),
+                kind: ObjectStoreKind::Local,
+            },
+            Connection::Local(LocalConnection {}),
+        )
+        .unwrap();
+        let prefix = dir.to_str().unwrap().trim_start_matches('/').to_string();
+
+        let mut listing = Listing::start(store, &prefix, None).unwrap();
+        let page = listing.page(1, 0, 2).await.unwrap();
+        let names: Vec<&str> = page
+            .items
+            .iter()
+            .map(|item| item.prefix.rsplit('/').next().unwrap())
+            .collect();
+
+        assert_eq!(names, vec!["a.txt", "b.txt"]);
+        assert_eq!(page.next_token, Some(String::from("1:2")));
+        let page = listing.page(1, 2, 2).await.unwrap();
+        assert_eq!(page.items.len(), 1);
+        assert!(matches!(page.items[0].kind, ContentKind::Directory));
+        assert_eq!(page.total, Some(3));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn detects_compressed_formats() {
+        assert_eq!(ContentFormat::detect("a/b.parquet"), ContentFormat::Parquet);
+        assert_eq!(ContentFormat::detect("a/b.CSV.gz"), ContentFormat::Csv);
+        assert_eq!(ContentFormat::detect("a/b"), ContentFormat::Unknown);
+    }
+}
@@ -18,6 +18,30 @@ pub enum Error {
     #[error("not found `{0}`")]
     NotFound(String),
 
+    #[error("prefix `{0}` is not empty")]
+    PrefixNotEmpty(String),
+
+    #[error("query timed out after {0} seconds")]
+    QueryTimeout(u64),
+
+    #[error("invalid function: {0}")]
+    InvalidFunction(String),
+
+    #[error("invalid argument: {0}")]
+    InvalidArgument(String),
+
+    #[error("invalid settings: {0}")]
+    InvalidSettings(String),
+
+    #[error("store `{0}` does not keep object versions")]
+    UnversionedStore(String),
+
+    #[error("failed to list object versions: {0}")]
+    ObjectVersions(String),
+
+    #[error("invalid page token `{0}`")]
+    InvalidPageToken(String),
+
     #[error(transparent)]
     Serde(#[from] serde_json::Error),
 
@@ -38,6 +62,15 @@ pub enum Error {
 
     #[error(transparent)]
     ArrowError(#[from] datafusion::arrow::error::ArrowError),
+
+    #[error(transparent)]
+    ParquetError(#[from] datafusion::parquet::errors::ParquetError),
+
+    #[error(transparent)]
+    Glob(#[from] globset::Error),
+
+    #[error(transparent)]
+    Regex(#[from] regex::Error),
 }
 
 impl serde::Serialize for Error {
@@ -0,0 +1,208 @@
+use std::sync::Arc;
+
+use datafusion::{
+    arrow::datatypes::Schema,
+    parquet::{
+        arrow::{
+            async_reader::{AsyncFileReader, ParquetObjectReader},
+            parquet_to_arrow_schema, ARROW_SCHEMA_META_KEY,
+        },
+        file::{
+            metadata::{ColumnChunkMetaData, ParquetMetaData, RowGroupMetaData},
+            statistics::Statistics,
+        },
+    },
+};
+use object_store::path::Path;
+use tracing::debug;
+
+use crate::{errors::Error, state::store::ObjectStore};
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct ColumnStatistics {
+    pub null_count: u64,
+    pub distinct_count: Option<u64>,
+    pub min: Option<String>,
+    pub max: Option<String>,
+}
+
+impl ColumnStatistics {
+    fn new(statistics: &Statistics) -> Self {
+        let (min, max) = if statistics.has_min_max_set() {
+            let (min, max) = match statistics {
+                Statistics::Boolean(s) => (s.min().to_string(), s.max().to_string()),
+                Statistics::Int32(s) => (s.min().to_string(), s.max().to_string()),
+                Statistics::Int64(s) => (s.min().to_string(), s.max().to_string()),
+                Statistics::Int96(s) => (format!("{:?}", s.min()), format!("{:?}", s.max())),
+                Statistics::Float(s) => (s.min().to_string(), s.max().to_string()),
+                Statistics::Double(s) => (s.min().to_string(), s.max().to_string()),
+                Statistics::ByteArray(s) => {
+                    (format_bytes(s.min().data()), format_bytes(s.max().data()))
+                }
+                Statistics::FixedLenByteArray(s) => {
+                    (format_bytes(s.min().data()), format_bytes(s.max().data()))
+                }
+            };
+            (Some(min), Some(max))
+        } else {
+            (None, None)
+        };
+
+        Self {
+            null_count: statistics.null_count(),
+            distinct_count: statistics.distinct_count(),
+            min,
+            max,
+        }
+    }
+}
+
+/// byte array statistics are usually strings, anything else is shown as hex
+fn format_bytes(bytes: &[u8]) -> String {
+    match std::str::from_utf8(bytes) {
+        Ok(value) => value.to_string(),
+        Err(_) => bytes.iter().map(|byte| format!("{:02x}", byte)).collect(),
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct ColumnChunkInspection {
+    pub path: String,
+    pub physical_type: String,
+    pub compression: String,
+    pub encodings: Vec<String>,
+    pub num_values: i64,
+    pub compressed_size: i64,
+    pub uncompressed_size: i64,
+    pub statistics: Option<ColumnStatistics>,
+    pub has_column_index: bool,
+    pub has_offset_index: bool,
+    pub has_bloom_filter: bool,
+}
+
+impl ColumnChunkInspection {
+    fn new(column: &ColumnChunkMetaData) -> Self {
+        Self {
+            path: column.column_path().string(),
+            physical_type: column.column_type().to_string(),
+            compression: column.compression().to_string(),
+            encodings: column
+                .encodings()
+                .iter()
+                .map(|encoding| encoding.to_string())
+                .collect(),
+            num_values: column.num_values(),
+            compressed_size: column.compressed_size(),
+            uncompressed_size: column.uncompressed_size(),
+            statistics: column.statistics().map(ColumnStatistics::new),
+            has_column_index: column.column_index_offset().is_some(),
+            has_offset_index: column.offset_index_offset().is_some(),
+            has_bloom_filter: column.bloom_filter_offset().is_some(),
+        }
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct RowGroupInspection {
+    pub num_rows: i64,
+    pub total_byte_size: i64,
+    pub compressed_size: i64,
+    pub columns: Vec<ColumnChunkInspection>,
+}
+
+impl RowGroupInspection {
+    fn new(row_group: &RowGroupMetaData) -> Self {
+        Self {
+            num_rows: row_group.num_rows(),
+            total_byte_size: row_group.total_byte_size(),
+            compressed_size: row_group.compressed_size(),
+            columns: row_group
+                .columns()
+                .iter()
+                .map(ColumnChunkInspection::new)
+                .collect(),
+        }
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct KeyValue {
+    pub key: String,
+    pub value: Option<String>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct ParquetInspection {
+    pub location: String,
+    pub size: usize,
+    pub version: i32,
+    pub created_by: Option<String>,
+    pub num_rows: i64,
+    pub num_row_groups: usize,
+    pub row_groups: Vec<RowGroupInspection>,
+    pub key_value_metadata: Vec<KeyValue>,
+    /// true if the schema was written by arrow and embedded in the key value metadata
+    pub has_arrow_schema: bool,
+    pub schema: Arc<Schema>,
+}
+
+impl ParquetInspection {
+    fn new(location: &Path, size: usize, metadata: &ParquetMetaData) -> Result<Self, Error> {
+        let file_metadata = metadata.file_metadata();
+        let key_value_metadata: Vec<KeyValue> = file_metadata
+            .key_value_metadata()
+            .map(|items| {
+                items
+                    .iter()
+                    .map(|item| KeyValue {
+                        key: item.key.clone(),
+                        value: item.value.clone(),
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        let has_arrow_schema = key_value_metadata
+            .iter()
+            .any(|item| item.key == ARROW_SCHEMA_META_KEY);
+
+        let schema = parquet_to_arrow_schema(
+            file_metadata.schema_descr(),
+            file_metadata.key_value_metadata(),
+        )?;
+
+        Ok(Self {
+            location: location.to_string(),
+            size,
+            version: file_metadata.version(),
+            created_by: file_metadata.created_by().map(String::from),
+            num_rows: file_metadata.num_rows(),
+            num_row_groups: metadata.num_row_groups(),
+            row_groups: metadata
+                .row_groups()
+                .iter()
+                .map(RowGroupInspection::new)
+                .collect(),
+            key_value_metadata,
+            has_arrow_schema,
+            schema: Arc::new(schema),
+        })
+    }
+
+    /// reads the footer of a parquet file, the data pages are never fetched
+    #[tracing::instrument(name = "inspecting parquet file", skip(store), fields(
+        store = store.metadata.id
+    ))]
+    pub async fn load(store: &ObjectStore, location: &Path) -> Result<Self, Error> {
+        let meta = store.client.head(location).await?;
+        let size = meta.size;
+
+        let mut reader = ParquetObjectReader::new(store.client.clone(), meta);
+        let metadata = reader.get_metadata().await?;
+        debug!(
+            num_row_groups = metadata.num_row_groups(),
+            "loaded parquet metadata"
+        );
+
+        Self::new(location, size, &metadata)
+    }
+}
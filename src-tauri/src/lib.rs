@@ -1,8 +1,11 @@
 pub mod commands;
 pub mod content;
 pub mod errors;
+pub mod inspect;
 pub mod messages;
+pub mod profile;
 pub mod state;
 pub use state::events;
 pub mod query;
+pub mod search;
 pub mod tracing;
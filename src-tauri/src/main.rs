@@ -4,46 +4,97 @@
 use app::{
     commands,
     errors::Error,
-    state::{store::get_home_dir, App, Config},
+    state::{settings::Settings, store::get_home_dir, App, Config},
     tracing::{get_subscriber, init_subscriber},
 };
 use std::{
     path::{Path as StdPath, PathBuf},
     sync::Arc,
+    time::Duration,
 };
 
 use tauri::Manager;
+use tracing::warn;
+
+/// how often the settings file is checked for changes
+const SETTINGS_POLL_INTERVAL: Duration = Duration::from_secs(2);
 
 #[tokio::main]
 async fn main() -> Result<(), Error> {
-    let subscriber = get_subscriber("file_fusion".into(), "info".into());
-    init_subscriber(subscriber).expect("failed to init subscriber");
-
     let base = get_home_dir().expect("failed to get home dir");
     let base = PathBuf::try_from(base).expect("failed to parse home dir");
     let events_file = base.join(StdPath::new(".config/file-fusion/events"));
+    let settings_file = base.join(StdPath::new(".config/file-fusion/settings.json"));
+    let runtime_file = base.join(StdPath::new(".config/file-fusion/runtime.json"));
+    let functions_dir = base.join(StdPath::new(".config/file-fusion/functions"));
 
-    let config = Config { events_file };
-    let app = App::new(config);
+    let log_level = Settings::load(&settings_file)
+        .map(|settings| settings.log_level)
+        .unwrap_or_else(|_| Settings::default().log_level);
+    let (subscriber, log_filter) = get_subscriber("file_fusion".into(), log_level);
+    init_subscriber(subscriber).expect("failed to init subscriber");
+
+    let config = Config {
+        events_file,
+        settings_file,
+        runtime_file,
+        functions_dir,
+        log_filter: Some(log_filter),
+    };
+    let app = App::new(config)?;
     app.sync().await?;
 
     let app = Arc::new(app);
+    #[cfg(debug_assertions)] // only read on debug builds, where the devtools can be opened
+    let open_devtools = app.get_settings().await.open_devtools;
+
+    let watched = app.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(SETTINGS_POLL_INTERVAL);
+        loop {
+            interval.tick().await;
+            if let Err(e) = watched.reload_settings().await {
+                warn!(?e, "failed to reload settings");
+            }
+        }
+    });
 
     tauri::Builder::default()
         .manage(app)
         .invoke_handler(tauri::generate_handler![
             commands::home_dir,
             commands::contents,
+            commands::list_contents,
+            commands::inspect_parquet,
+            commands::search,
+            commands::cancel_search,
+            commands::profile_buffer,
+            commands::cancel_profile,
+            commands::get_profile,
             commands::storage,
             commands::storages,
+            commands::test_connection,
             commands::update,
             commands::query,
+            commands::query_ipc,
+            commands::explain,
+            commands::summarize,
+            commands::diff,
+            commands::materialize,
             commands::get_buffers,
             commands::get_table,
+            commands::compare_schemas,
+            commands::refresh_schema,
+            commands::get_buffer_tables,
+            commands::get_functions,
+            commands::get_runtime_settings,
+            commands::update_runtime_settings,
+            commands::get_settings,
+            commands::update_settings,
         ])
         .setup(|app| {
             #[cfg(debug_assertions)] // only include this code on debug builds
-            {
+            if open_devtools {
                 let window = app.get_window("main").unwrap();
                 window.open_devtools();
             }
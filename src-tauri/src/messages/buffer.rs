@@ -1,3 +1,5 @@
+use crate::query::schemas::SchemaOverride;
+
 #[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
 pub struct FileSystemBufferMetadata {
     pub store: usize,
@@ -9,9 +11,43 @@ pub struct BufferMetadata {
     pub name: String,
     pub common_schema: bool,
     pub file_systems: Vec<FileSystemBufferMetadata>,
+    #[serde(default)]
+    pub schema_override: SchemaOverride,
+    /// reads the objects as they were at this time, in milliseconds since the unix epoch
+    #[serde(default)]
+    pub as_of: Option<i64>,
+    /// how long file listings of the buffer are reused, the cache setting when not set
+    #[serde(default)]
+    pub listing_ttl_secs: Option<u64>,
 }
 
 #[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
 pub struct Create {
     pub metadata: BufferMetadata,
 }
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct UpdateSchema {
+    pub buffer: usize,
+    pub schema_override: SchemaOverride,
+}
+
+/// pins the objects of a buffer to their versions at a time, `None` reads the latest versions
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct PinVersions {
+    pub buffer: usize,
+    pub as_of: Option<i64>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct Materialize {
+    pub name: String,
+    pub store: usize,
+    pub prefix: String,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct CreateView {
+    pub name: String,
+    pub statement: String,
+}
@@ -1,3 +1,5 @@
+use crate::query::functions::FunctionDefinition;
+
 pub mod buffer;
 pub mod store;
 
@@ -5,4 +7,8 @@ pub mod store;
 pub enum Messages {
     CreateObjectStore(store::Create),
     CreateBuffer(buffer::Create),
+    CreateView(buffer::CreateView),
+    UpdateBufferSchema(buffer::UpdateSchema),
+    PinBufferVersions(buffer::PinVersions),
+    CreateFunction(FunctionDefinition),
 }
@@ -0,0 +1,739 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use datafusion::{
+    arrow::{
+        array::{Array, ArrayRef, AsArray},
+        datatypes::{DataType, Float64Type},
+    },
+    common::ScalarValue,
+    dataframe::DataFrame,
+    logical_expr::{cast, Accumulator, Expr},
+    parquet::{
+        arrow::async_reader::{AsyncFileReader, ParquetObjectReader},
+        file::statistics::Statistics,
+    },
+    physical_expr::expressions::{MaxAccumulator, MinAccumulator},
+};
+use futures::StreamExt;
+use object_store::ObjectMeta;
+use tracing::{debug, info, warn};
+
+use crate::{errors::Error, state::store::ObjectStore};
+
+/// the name of the event emitted to the window while a profile is running
+pub const PROFILE_EVENT: &str = "profile";
+
+pub const HISTOGRAM_BUCKETS: usize = 20;
+
+/// the number of record batches scanned between progress events
+const BATCHES_PER_PROGRESS_EVENT: usize = 16;
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct ProfileRequest {
+    pub buffer: usize,
+    /// histograms always require scanning the numeric columns
+    pub histograms: bool,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProfileStage {
+    Footers,
+    Scan,
+    Histograms,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub enum ProfileEvent {
+    /// `done` and `total` count files while reading footers and rows while scanning
+    Progress {
+        profile: usize,
+        stage: ProfileStage,
+        done: u64,
+        total: Option<u64>,
+    },
+    Completed {
+        profile: usize,
+        buffer: usize,
+    },
+    Cancelled {
+        profile: usize,
+    },
+    Failed {
+        profile: usize,
+        error: String,
+    },
+}
+
+/// equal width buckets between the min and max of a column
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq)]
+pub struct Histogram {
+    pub min: f64,
+    pub max: f64,
+    pub counts: Vec<u64>,
+}
+
+impl Histogram {
+    pub fn new(min: f64, max: f64, buckets: usize) -> Self {
+        let buckets = if min < max { buckets.max(1) } else { 1 };
+
+        Self {
+            min,
+            max,
+            counts: vec![0; buckets],
+        }
+    }
+
+    pub fn add(&mut self, value: f64) {
+        if !(self.min..=self.max).contains(&value) {
+            return;
+        }
+
+        let width = (self.max - self.min) / self.counts.len() as f64;
+        let bucket = if width > 0.0 {
+            (((value - self.min) / width) as usize).min(self.counts.len() - 1)
+        } else {
+            0
+        };
+        self.counts[bucket] += 1;
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProfileSource {
+    /// computed from the statistics in the parquet footers without reading the data
+    FooterStatistics,
+    Scan,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct ColumnProfile {
+    pub name: String,
+    pub data_type: DataType,
+    /// the number of non null values
+    pub count: u64,
+    pub null_count: u64,
+    pub min: Option<String>,
+    pub max: Option<String>,
+    pub histogram: Option<Histogram>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct BufferProfile {
+    pub buffer: usize,
+    /// milliseconds since the unix epoch
+    pub created_at: i64,
+    pub num_files: usize,
+    pub num_rows: u64,
+    pub source: ProfileSource,
+    pub columns: Vec<ColumnProfile>,
+}
+
+/// the min and max of a parquet column chunk, `None` for int96 timestamps
+fn statistics_bounds(statistics: &Statistics) -> Option<(ScalarValue, ScalarValue)> {
+    let bytes = |value: &[u8]| match std::str::from_utf8(value) {
+        Ok(value) => ScalarValue::Utf8(Some(value.to_string())),
+        Err(_) => ScalarValue::Binary(Some(value.to_vec())),
+    };
+
+    let bounds = match statistics {
+        Statistics::Boolean(s) => (
+            ScalarValue::Boolean(Some(*s.min())),
+            ScalarValue::Boolean(Some(*s.max())),
+        ),
+        Statistics::Int32(s) => (
+            ScalarValue::Int32(Some(*s.min())),
+            ScalarValue::Int32(Some(*s.max())),
+        ),
+        Statistics::Int64(s) => (
+            ScalarValue::Int64(Some(*s.min())),
+            ScalarValue::Int64(Some(*s.max())),
+        ),
+        Statistics::Float(s) => (
+            ScalarValue::Float32(Some(*s.min())),
+            ScalarValue::Float32(Some(*s.max())),
+        ),
+        Statistics::Double(s) => (
+            ScalarValue::Float64(Some(*s.min())),
+            ScalarValue::Float64(Some(*s.max())),
+        ),
+        Statistics::ByteArray(s) => (bytes(s.min().data()), bytes(s.max().data())),
+        Statistics::FixedLenByteArray(s) => (bytes(s.min().data()), bytes(s.max().data())),
+        Statistics::Int96(_) => return None,
+    };
+
+    Some(bounds)
+}
+
+enum Footers {
+    Complete {
+        num_rows: u64,
+    },
+    MissingStatistics,
+    /// a file is not a parquet file or could not be read
+    Unreadable,
+    Cancelled,
+}
+
+struct ColumnAccumulator {
+    name: String,
+    data_type: DataType,
+    count: u64,
+    null_count: u64,
+    /// dropped when the values can not be compared, e.g. for nested types
+    min: Option<MinAccumulator>,
+    max: Option<MaxAccumulator>,
+}
+
+impl ColumnAccumulator {
+    fn new(name: &str, data_type: &DataType) -> Self {
+        Self {
+            name: name.to_string(),
+            data_type: data_type.clone(),
+            count: 0,
+            null_count: 0,
+            min: MinAccumulator::try_new(data_type).ok(),
+            max: MaxAccumulator::try_new(data_type).ok(),
+        }
+    }
+
+    fn update(&mut self, array: &ArrayRef) {
+        let null_count = array.null_count() as u64;
+        self.null_count += null_count;
+        self.count += array.len() as u64 - null_count;
+        self.update_min_max(array);
+    }
+
+    fn update_min_max(&mut self, array: &ArrayRef) {
+        let values = [array.clone()];
+        let min = self.min.as_mut().map(|min| min.update_batch(&values));
+        let max = self.max.as_mut().map(|max| max.update_batch(&values));
+
+        if matches!(min, Some(Err(_))) || matches!(max, Some(Err(_))) {
+            warn!(column = %self.name, "failed to compare values, dropping min and max");
+            self.min = None;
+            self.max = None;
+        }
+    }
+
+    /// false if the column chunk has no statistics for its values
+    fn update_statistics(&mut self, num_values: i64, statistics: Option<&Statistics>) -> bool {
+        let statistics = match statistics {
+            Some(statistics) => statistics,
+            None => return false,
+        };
+
+        let null_count = statistics.null_count();
+        let count = (num_values.max(0) as u64).saturating_sub(null_count);
+        self.null_count += null_count;
+        self.count += count;
+
+        if !statistics.has_min_max_set() {
+            return count == 0;
+        }
+
+        // the footers store physical values, e.g. dates as int32
+        let bounds = statistics_bounds(statistics).and_then(|(min, max)| {
+            let min = min.cast_to(&self.data_type).ok()?;
+            let max = max.cast_to(&self.data_type).ok()?;
+            ScalarValue::iter_to_array(vec![min, max]).ok()
+        });
+        match bounds {
+            Some(bounds) => self.update_min_max(&bounds),
+            None => {
+                self.min = None;
+                self.max = None;
+            }
+        }
+
+        true
+    }
+
+    fn bounds(&mut self) -> Option<(f64, f64)> {
+        let value = |scalar: Option<ScalarValue>| match scalar?.cast_to(&DataType::Float64) {
+            Ok(ScalarValue::Float64(Some(value))) if value.is_finite() => Some(value),
+            _ => None,
+        };
+
+        let min = value(self.min.as_mut().and_then(|min| min.evaluate().ok()))?;
+        let max = value(self.max.as_mut().and_then(|max| max.evaluate().ok()))?;
+
+        Some((min, max))
+    }
+
+    fn finish(mut self, histogram: Option<Histogram>) -> ColumnProfile {
+        let display = |scalar: Option<ScalarValue>| {
+            scalar
+                .filter(|scalar| !scalar.is_null())
+                .map(|scalar| scalar.to_string())
+        };
+
+        ColumnProfile {
+            min: display(self.min.as_mut().and_then(|min| min.evaluate().ok())),
+            max: display(self.max.as_mut().and_then(|max| max.evaluate().ok())),
+            name: self.name,
+            data_type: self.data_type,
+            count: self.count,
+            null_count: self.null_count,
+            histogram,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ProfileJob {
+    id: usize,
+    request: ProfileRequest,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl ProfileJob {
+    pub fn new(id: usize, request: ProfileRequest) -> Self {
+        Self {
+            id,
+            request,
+            cancelled: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    pub fn buffer(&self) -> usize {
+        self.request.buffer
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    fn progress(&self, stage: ProfileStage, done: u64, total: Option<u64>) -> ProfileEvent {
+        ProfileEvent::Progress {
+            profile: self.id,
+            stage,
+            done,
+            total,
+        }
+    }
+
+    fn accumulators(df: &DataFrame) -> Vec<ColumnAccumulator> {
+        df.schema()
+            .fields()
+            .iter()
+            .map(|field| ColumnAccumulator::new(field.name(), field.data_type()))
+            .collect()
+    }
+
+    /// profiles the buffer's files, or scans `df` when the footers are missing statistics.
+    /// returns `None` when the job was cancelled
+    #[tracing::instrument(name = "running profile", skip(self, files, df, emit), fields(
+        profile = self.id,
+        buffer = self.request.buffer
+    ))]
+    pub async fn run<F>(
+        &self,
+        files: Vec<(ObjectStore, ObjectMeta)>,
+        df: DataFrame,
+        emit: F,
+    ) -> Result<Option<BufferProfile>, Error>
+    where
+        F: Fn(ProfileEvent),
+    {
+        let mut columns = Self::accumulators(&df);
+        let mut num_rows = 0;
+        let mut source = ProfileSource::Scan;
+
+        // the files are listed with the extension of the buffer tables, e.g. without `_SUCCESS`
+        // markers or checksum files
+        let has_footers = !files.is_empty();
+        if has_footers {
+            match self.read_footers(&files, &mut columns, &emit).await? {
+                Footers::Complete { num_rows: rows } => {
+                    num_rows = rows;
+                    source = ProfileSource::FooterStatistics;
+                }
+                Footers::MissingStatistics => {
+                    info!("footers are missing statistics, scanning files");
+                    columns = Self::accumulators(&df);
+                }
+                Footers::Unreadable => {
+                    info!("failed to read footers, scanning files");
+                    columns = Self::accumulators(&df);
+                }
+                Footers::Cancelled => return Ok(None),
+            }
+        }
+
+        if source == ProfileSource::Scan {
+            match self.scan(&df, &mut columns, &emit).await? {
+                Some(rows) => num_rows = rows,
+                None => return Ok(None),
+            }
+        }
+
+        let mut histograms: Vec<Option<Histogram>> = columns.iter().map(|_| None).collect();
+        if self.request.histograms {
+            let completed = self
+                .histograms(&df, &mut columns, &mut histograms, num_rows, &emit)
+                .await?;
+            if !completed {
+                return Ok(None);
+            }
+        }
+
+        let created_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_millis() as i64);
+
+        Ok(Some(BufferProfile {
+            buffer: self.request.buffer,
+            created_at,
+            num_files: files.len(),
+            num_rows,
+            source,
+            columns: columns
+                .into_iter()
+                .zip(histograms)
+                .map(|(column, histogram)| column.finish(histogram))
+                .collect(),
+        }))
+    }
+
+    async fn read_footers<F>(
+        &self,
+        files: &[(ObjectStore, ObjectMeta)],
+        columns: &mut [ColumnAccumulator],
+        emit: &F,
+    ) -> Result<Footers, Error>
+    where
+        F: Fn(ProfileEvent),
+    {
+        // leaf column paths only match the field names of flat schemas
+        if columns.iter().any(|column| column.data_type.is_nested()) {
+            return Ok(Footers::MissingStatistics);
+        }
+
+        let mut num_rows = 0;
+        for (i, (store, meta)) in files.iter().enumerate() {
+            if self.is_cancelled() {
+                info!("profile cancelled");
+                return Ok(Footers::Cancelled);
+            }
+
+            let mut reader = ParquetObjectReader::new(store.client.clone(), meta.clone());
+            let metadata = match reader.get_metadata().await {
+                Ok(metadata) => metadata,
+                Err(e) => {
+                    warn!(?e, file = %meta.location, "failed to read footer");
+                    return Ok(Footers::Unreadable);
+                }
+            };
+            num_rows += metadata.file_metadata().num_rows().max(0) as u64;
+
+            for row_group in metadata.row_groups() {
+                for chunk in row_group.columns() {
+                    let path = chunk.column_path().string();
+                    let column = columns.iter_mut().find(|column| column.name == path);
+                    let has_statistics = match column {
+                        Some(column) => {
+                            column.update_statistics(chunk.num_values(), chunk.statistics())
+                        }
+                        None => true,
+                    };
+
+                    if !has_statistics {
+                        debug!(file = %meta.location, column = %path, "missing statistics");
+                        return Ok(Footers::MissingStatistics);
+                    }
+                }
+            }
+
+            emit(self.progress(
+                ProfileStage::Footers,
+                i as u64 + 1,
+                Some(files.len() as u64),
+            ));
+        }
+
+        Ok(Footers::Complete { num_rows })
+    }
+
+    /// returns the number of rows scanned, `None` when the job was cancelled
+    async fn scan<F>(
+        &self,
+        df: &DataFrame,
+        columns: &mut [ColumnAccumulator],
+        emit: &F,
+    ) -> Result<Option<u64>, Error>
+    where
+        F: Fn(ProfileEvent),
+    {
+        let mut num_rows = 0;
+        let mut stream = df.clone().execute_stream().await?;
+
+        let mut num_batches = 0;
+        while let Some(batch) = stream.next().await {
+            if self.is_cancelled() {
+                info!(num_rows, "profile cancelled");
+                return Ok(None);
+            }
+
+            let batch = batch?;
+            for (column, array) in columns.iter_mut().zip(batch.columns()) {
+                column.update(array);
+            }
+            num_rows += batch.num_rows() as u64;
+
+            num_batches += 1;
+            if num_batches % BATCHES_PER_PROGRESS_EVENT == 0 {
+                emit(self.progress(ProfileStage::Scan, num_rows, None));
+            }
+        }
+
+        emit(self.progress(ProfileStage::Scan, num_rows, Some(num_rows)));
+        Ok(Some(num_rows))
+    }
+
+    /// fills the histograms of the numeric columns with a min and max in a single scan, returns
+    /// false when the job was cancelled
+    async fn histograms<F>(
+        &self,
+        df: &DataFrame,
+        columns: &mut [ColumnAccumulator],
+        histograms: &mut [Option<Histogram>],
+        num_rows: u64,
+        emit: &F,
+    ) -> Result<bool, Error>
+    where
+        F: Fn(ProfileEvent),
+    {
+        let mut numeric = Vec::new();
+        for (i, column) in columns.iter_mut().enumerate() {
+            if !column.data_type.is_numeric() {
+                continue;
+            }
+            if let Some((min, max)) = column.bounds() {
+                histograms[i] = Some(Histogram::new(min, max, HISTOGRAM_BUCKETS));
+                numeric.push(i);
+            }
+        }
+
+        if numeric.is_empty() {
+            return Ok(true);
+        }
+
+        // the accumulators are in the order of the schema fields
+        let fields = df.schema().fields();
+        let projection = numeric
+            .iter()
+            .map(|i| {
+                cast(
+                    Expr::Column(fields[*i].qualified_column()),
+                    DataType::Float64,
+                )
+            })
+            .collect();
+        let mut stream = df.clone().select(projection)?.execute_stream().await?;
+
+        let mut rows_scanned = 0;
+        let mut num_batches = 0;
+        while let Some(batch) = stream.next().await {
+            if self.is_cancelled() {
+                info!(rows_scanned, "profile cancelled");
+                return Ok(false);
+            }
+
+            let batch = batch?;
+            for (array, i) in batch.columns().iter().zip(numeric.iter()) {
+                if let Some(histogram) = histograms[*i].as_mut() {
+                    for value in array.as_primitive::<Float64Type>().iter().flatten() {
+                        histogram.add(value);
+                    }
+                }
+            }
+            rows_scanned += batch.num_rows() as u64;
+
+            num_batches += 1;
+            if num_batches % BATCHES_PER_PROGRESS_EVENT == 0 {
+                emit(self.progress(ProfileStage::Histograms, rows_scanned, Some(num_rows)));
+            }
+        }
+
+        emit(self.progress(ProfileStage::Histograms, rows_scanned, Some(num_rows)));
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod test_histogram {
+    use super::*;
+
+    #[test]
+    fn buckets_values() {
+        let mut histogram = Histogram::new(0.0, 10.0, 5);
+        for value in [0.0, 1.9, 2.0, 9.9, 10.0, 11.0, f64::NAN] {
+            histogram.add(value);
+        }
+
+        assert_eq!(histogram.counts, vec![2, 1, 0, 0, 2]);
+    }
+
+    #[test]
+    fn uses_a_single_bucket_for_constant_columns() {
+        let mut histogram = Histogram::new(3.0, 3.0, 5);
+        histogram.add(3.0);
+        histogram.add(3.0);
+
+        assert_eq!(histogram.counts, vec![2]);
+    }
+}
+
+#[cfg(test)]
+mod test_profile {
+    use datafusion::{
+        arrow::{
+            array::Int64Array,
+            datatypes::{Field, Schema},
+            record_batch::RecordBatch,
+        },
+        parquet::arrow::ArrowWriter,
+        prelude::{ParquetReadOptions, SessionContext},
+    };
+    use object_store::path::Path;
+
+    use crate::{
+        query::{FileSystemBuffer, PrefixPattern},
+        state::store::{Connection, LocalConnection, Metadata, ObjectStoreKind},
+    };
+
+    use super::*;
+
+    struct Fixture {
+        dir: std::path::PathBuf,
+        store: ObjectStore,
+        files: Vec<(ObjectStore, ObjectMeta)>,
+        df: DataFrame,
+    }
+
+    /// a directory with a parquet file of three rows and a `_SUCCESS` marker
+    async fn fixture() -> Fixture {
+        let dir = std::env::temp_dir().join(format!("file-fusion-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("_SUCCESS"), "").unwrap();
+
+        let schema = Arc::new(Schema::new(vec![Field::new("id", DataType::Int64, false)]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![Arc::new(Int64Array::from(vec![1, 2, 3]))],
+        )
+        .unwrap();
+        let file = std::fs::File::create(dir.join("data.parquet")).unwrap();
+        let mut writer = ArrowWriter::try_new(file, schema, None).unwrap();
+        writer.write(&batch).unwrap();
+        writer.close().unwrap();
+
+        let store = ObjectStore::new(
+            Metadata {
+                id: 0,
+                name: String::from("Local"),
+                prefix: String::new(),
+                kind: ObjectStoreKind::Local,
+            },
+            Connection::Local(LocalConnection {}),
+        )
+        .unwrap();
+        let prefix = dir.to_str().unwrap().trim_start_matches('/');
+        let file_system =
+            FileSystemBuffer::new(store.clone(), &vec![PrefixPattern::parse(prefix).unwrap()]);
+        let files = file_system
+            .list_files()
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|meta| (store.clone(), meta))
+            .collect();
+
+        let df = SessionContext::new()
+            .read_parquet(dir.to_str().unwrap(), ParquetReadOptions::default())
+            .await
+            .unwrap();
+
+        Fixture {
+            dir,
+            store,
+            files,
+            df,
+        }
+    }
+
+    fn job() -> ProfileJob {
+        ProfileJob::new(
+            1,
+            ProfileRequest {
+                buffer: 0,
+                histograms: false,
+            },
+        )
+    }
+
+    #[tokio::test]
+    async fn reads_footers_of_parquet_files_only() {
+        let fixture = fixture().await;
+
+        let profile = job()
+            .run(fixture.files, fixture.df, |_| {})
+            .await
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(profile.source, ProfileSource::FooterStatistics);
+        assert_eq!(profile.num_files, 1);
+        assert_eq!(profile.num_rows, 3);
+        assert_eq!(profile.columns[0].min, Some(String::from("1")));
+        assert_eq!(profile.columns[0].max, Some(String::from("3")));
+
+        std::fs::remove_dir_all(fixture.dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn scans_when_a_footer_can_not_be_read() {
+        let mut fixture = fixture().await;
+        let missing = ObjectMeta {
+            location: Path::parse(
+                fixture
+                    .dir
+                    .join("missing.parquet")
+                    .to_str()
+                    .unwrap()
+                    .trim_start_matches('/'),
+            )
+            .unwrap(),
+            last_modified: chrono::Utc::now(),
+            size: 8,
+            e_tag: None,
+        };
+        fixture.files.push((fixture.store.clone(), missing));
+
+        let profile = job()
+            .run(fixture.files, fixture.df, |_| {})
+            .await
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(profile.source, ProfileSource::Scan);
+        assert_eq!(profile.num_rows, 3);
+        assert_eq!(profile.columns[0].count, 3);
+
+        std::fs::remove_dir_all(fixture.dir).unwrap();
+    }
+}
@@ -0,0 +1,458 @@
+use datafusion::{
+    arrow::{
+        self,
+        array::{AsArray, BooleanArray},
+        compute::filter_record_batch,
+        record_batch::RecordBatch,
+    },
+    common::JoinType,
+    dataframe::DataFrame,
+    logical_expr::{binary_expr, ident, lit, Expr, Operator},
+};
+use futures::TryStreamExt;
+use serde_json::{Map, Value};
+
+use crate::errors::Error;
+
+use super::Query;
+
+/// the number of rows sampled per kind of change when not set
+pub const DEFAULT_DIFF_SAMPLE: usize = 10;
+
+const OLD_PRESENT: &str = "__old_present";
+const NEW_PRESENT: &str = "__new_present";
+const ADDED: &str = "__added";
+const REMOVED: &str = "__removed";
+const MATCHED: &str = "__matched";
+const CHANGED: &str = "__changed";
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub enum DiffSource {
+    Buffer(usize),
+    Query(Query),
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct DiffRequest {
+    pub old: DiffSource,
+    pub new: DiffSource,
+    /// rows with equal keys are compared, keys should be unique on both sides. a null key never
+    /// equals another key, so rows with null keys are counted as removed and added
+    pub keys: Vec<String>,
+    pub sample: Option<usize>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct ColumnChanges {
+    pub name: String,
+    pub num_changed: i64,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct ValueChange {
+    pub column: String,
+    pub old: Value,
+    pub new: Value,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct ChangedRow {
+    pub key: Map<String, Value>,
+    pub changes: Vec<ValueChange>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct RowDiff {
+    pub keys: Vec<String>,
+    /// columns in both sides that are compared
+    pub columns: Vec<ColumnChanges>,
+    pub old_only_columns: Vec<String>,
+    pub new_only_columns: Vec<String>,
+    pub num_added: i64,
+    pub num_removed: i64,
+    pub num_changed: i64,
+    pub num_unchanged: i64,
+    pub added: Vec<Map<String, Value>>,
+    pub removed: Vec<Map<String, Value>>,
+    pub changed: Vec<ChangedRow>,
+}
+
+fn old_name(name: &str) -> String {
+    format!("__old_{}", name)
+}
+
+fn new_name(name: &str) -> String {
+    format!("__new_{}", name)
+}
+
+fn changed_name(column: usize) -> String {
+    format!("__changed_{}", column)
+}
+
+fn column_names(df: &DataFrame) -> Vec<String> {
+    df.schema()
+        .fields()
+        .iter()
+        .map(|field| field.name().clone())
+        .collect()
+}
+
+/// renames every column with `rename` and adds a column that is null for unmatched rows
+fn prefixed(df: DataFrame, rename: fn(&str) -> String, present: &str) -> Result<DataFrame, Error> {
+    let mut exprs: Vec<Expr> = column_names(&df)
+        .iter()
+        .map(|name| ident(name).alias(rename(name)))
+        .collect();
+    exprs.push(lit(true).alias(present));
+
+    Ok(df.select(exprs)?)
+}
+
+fn flags<'a>(batch: &'a RecordBatch, name: &str) -> Result<&'a BooleanArray, Error> {
+    batch
+        .column_by_name(name)
+        .and_then(|array| array.as_boolean_opt())
+        .ok_or_else(|| Error::NotFound(format!("diff column {}", name)))
+}
+
+/// rows of the joined batches with a flag set, only the first `sample` rows are kept
+struct Sample {
+    flag: String,
+    num_rows: i64,
+    batches: Vec<RecordBatch>,
+}
+
+impl Sample {
+    fn new(flag: impl Into<String>) -> Self {
+        Self {
+            flag: flag.into(),
+            num_rows: 0,
+            batches: Vec::new(),
+        }
+    }
+
+    fn add(&mut self, batch: &RecordBatch, sample: usize) -> Result<(), Error> {
+        let flags = flags(batch, &self.flag)?;
+        let num_sampled: usize = self.batches.iter().map(RecordBatch::num_rows).sum();
+        if num_sampled < sample && flags.true_count() > 0 {
+            let rows = filter_record_batch(batch, flags)?;
+            let len = rows.num_rows().min(sample - num_sampled);
+            self.batches.push(rows.slice(0, len));
+        }
+        self.num_rows += flags.true_count() as i64;
+
+        Ok(())
+    }
+
+    fn json_rows(&self) -> Result<Vec<Map<String, Value>>, Error> {
+        let batches: Vec<_> = self.batches.iter().collect();
+
+        Ok(arrow::json::writer::record_batches_to_json_rows(
+            &batches[..],
+        )?)
+    }
+
+    /// the sampled rows with the `rename`d columns under their own name
+    fn rows(
+        &self,
+        columns: &[String],
+        rename: fn(&str) -> String,
+    ) -> Result<Vec<Map<String, Value>>, Error> {
+        let rows = self
+            .json_rows()?
+            .into_iter()
+            .map(|mut row| {
+                columns
+                    .iter()
+                    .filter_map(|name| row.remove(&rename(name)).map(|value| (name.clone(), value)))
+                    .collect()
+            })
+            .collect();
+
+        Ok(rows)
+    }
+}
+
+impl RowDiff {
+    /// full outer joins both sides on the keys, rows without a match on the new side are
+    /// removed and rows without a match on the old side are added. the join is scanned once to
+    /// count the changes and sample the rows
+    pub async fn compute(
+        old_df: DataFrame,
+        new_df: DataFrame,
+        keys: Vec<String>,
+        sample: usize,
+    ) -> Result<Self, Error> {
+        let old_columns = column_names(&old_df);
+        let new_columns = column_names(&new_df);
+
+        if keys.is_empty() {
+            return Err(Error::InvalidArgument(String::from(
+                "at least one key column is required",
+            )));
+        }
+        for key in keys.iter() {
+            if !old_columns.contains(key) || !new_columns.contains(key) {
+                return Err(Error::InvalidArgument(format!(
+                    "key column `{}` is not on both sides",
+                    key
+                )));
+            }
+        }
+
+        let compared: Vec<String> = old_columns
+            .iter()
+            .filter(|name| !keys.contains(name) && new_columns.contains(name))
+            .cloned()
+            .collect();
+        let old_only_columns = old_columns
+            .iter()
+            .filter(|name| !new_columns.contains(name))
+            .cloned()
+            .collect();
+        let new_only_columns = new_columns
+            .iter()
+            .filter(|name| !old_columns.contains(name))
+            .cloned()
+            .collect();
+
+        let old_keys: Vec<String> = keys.iter().map(|key| old_name(key)).collect();
+        let new_keys: Vec<String> = keys.iter().map(|key| new_name(key)).collect();
+        let joined = prefixed(old_df, old_name, OLD_PRESENT)?.join(
+            prefixed(new_df, new_name, NEW_PRESENT)?,
+            JoinType::Full,
+            &old_keys.iter().map(String::as_str).collect::<Vec<_>>(),
+            &new_keys.iter().map(String::as_str).collect::<Vec<_>>(),
+            None,
+        )?;
+
+        let is_removed = ident(NEW_PRESENT).is_null();
+        let is_added = ident(OLD_PRESENT).is_null();
+        let is_matched = ident(OLD_PRESENT)
+            .is_not_null()
+            .and(ident(NEW_PRESENT).is_not_null());
+        let column_changed = |name: &str| {
+            is_matched.clone().and(binary_expr(
+                ident(old_name(name)),
+                Operator::IsDistinctFrom,
+                ident(new_name(name)),
+            ))
+        };
+        let is_changed = compared
+            .iter()
+            .map(|name| column_changed(name))
+            .reduce(Expr::or)
+            .unwrap_or(lit(false));
+
+        let mut exprs: Vec<Expr> = column_names(&joined).iter().map(ident).collect();
+        exprs.push(is_added.alias(ADDED));
+        exprs.push(is_removed.alias(REMOVED));
+        exprs.push(is_matched.clone().alias(MATCHED));
+        exprs.push(is_changed.alias(CHANGED));
+        for (i, name) in compared.iter().enumerate() {
+            exprs.push(column_changed(name).alias(changed_name(i)));
+        }
+
+        let mut added = Sample::new(ADDED);
+        let mut removed = Sample::new(REMOVED);
+        let mut changed = Sample::new(CHANGED);
+        let mut num_matched = 0;
+        let mut column_changes = vec![0; compared.len()];
+
+        let mut stream = joined.select(exprs)?.execute_stream().await?;
+        while let Some(batch) = stream.try_next().await? {
+            added.add(&batch, sample)?;
+            removed.add(&batch, sample)?;
+            changed.add(&batch, sample)?;
+
+            num_matched += flags(&batch, MATCHED)?.true_count() as i64;
+            for (i, num_changed) in column_changes.iter_mut().enumerate() {
+                *num_changed += flags(&batch, &changed_name(i))?.true_count() as i64;
+            }
+        }
+
+        let columns = compared
+            .iter()
+            .zip(column_changes)
+            .map(|(name, num_changed)| ColumnChanges {
+                name: name.clone(),
+                num_changed,
+            })
+            .collect();
+        let changed_rows = changed
+            .json_rows()?
+            .into_iter()
+            .map(|row| ChangedRow::new(row, &keys, &compared))
+            .collect();
+
+        Ok(Self {
+            columns,
+            old_only_columns,
+            new_only_columns,
+            num_added: added.num_rows,
+            num_removed: removed.num_rows,
+            num_changed: changed.num_rows,
+            num_unchanged: num_matched - changed.num_rows,
+            added: added.rows(&new_columns, new_name)?,
+            removed: removed.rows(&old_columns, old_name)?,
+            changed: changed_rows,
+            keys,
+        })
+    }
+}
+
+impl ChangedRow {
+    /// json rows leave out nulls, a missing value is a null
+    fn new(mut row: Map<String, Value>, keys: &[String], compared: &[String]) -> Self {
+        let key = keys
+            .iter()
+            .map(|key| {
+                let value = row.remove(&old_name(key)).unwrap_or(Value::Null);
+                (key.clone(), value)
+            })
+            .collect();
+
+        let changes = compared
+            .iter()
+            .filter_map(|column| {
+                let old = row.remove(&old_name(column)).unwrap_or(Value::Null);
+                let new = row.remove(&new_name(column)).unwrap_or(Value::Null);
+                if old == new {
+                    return None;
+                }
+
+                Some(ValueChange {
+                    column: column.clone(),
+                    old,
+                    new,
+                })
+            })
+            .collect();
+
+        Self { key, changes }
+    }
+}
+
+#[cfg(test)]
+mod test_diff {
+    use std::sync::Arc;
+
+    use datafusion::{
+        arrow::{
+            array::{ArrayRef, Int64Array, StringArray},
+            datatypes::{DataType, Field, Schema},
+        },
+        datasource::MemTable,
+        prelude::SessionContext,
+    };
+    use serde_json::json;
+
+    use super::*;
+
+    fn frame(
+        ctx: &SessionContext,
+        ids: Vec<Option<i64>>,
+        names: Vec<&str>,
+        extra: &str,
+    ) -> DataFrame {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int64, true),
+            Field::new("name", DataType::Utf8, false),
+            Field::new(extra, DataType::Utf8, false),
+        ]));
+        let num_rows = ids.len();
+        let columns: Vec<ArrayRef> = vec![
+            Arc::new(Int64Array::from(ids)),
+            Arc::new(StringArray::from(names)),
+            Arc::new(StringArray::from(vec![extra; num_rows])),
+        ];
+        let batch = RecordBatch::try_new(schema.clone(), columns).unwrap();
+        let table = MemTable::try_new(schema, vec![vec![batch]]).unwrap();
+
+        ctx.read_table(Arc::new(table)).unwrap()
+    }
+
+    #[tokio::test]
+    async fn counts_and_samples_changes() {
+        let ctx = SessionContext::new();
+        let old = frame(
+            &ctx,
+            vec![Some(1), Some(2), Some(3), None],
+            vec!["a", "b", "c", "n"],
+            "note",
+        );
+        let new = frame(
+            &ctx,
+            vec![Some(1), Some(2), Some(4), None],
+            vec!["a", "x", "d", "n"],
+            "email",
+        );
+
+        let diff = RowDiff::compute(old, new, vec![String::from("id")], 1)
+            .await
+            .unwrap();
+
+        assert_eq!(diff.old_only_columns, vec![String::from("note")]);
+        assert_eq!(diff.new_only_columns, vec![String::from("email")]);
+        assert_eq!(diff.columns.len(), 1);
+        assert_eq!(diff.columns[0].num_changed, 1);
+        assert_eq!(diff.num_changed, 1);
+        assert_eq!(diff.num_unchanged, 1);
+        // the rows with a null key never match
+        assert_eq!(diff.num_added, 2);
+        assert_eq!(diff.num_removed, 2);
+
+        // only `sample` rows are kept per kind of change
+        assert_eq!(diff.added.len(), 1);
+        assert_eq!(diff.removed.len(), 1);
+        assert_eq!(diff.changed.len(), 1);
+        assert_eq!(diff.changed[0].key.get("id"), Some(&json!(2)));
+        assert_eq!(diff.changed[0].changes[0].old, json!("b"));
+        assert_eq!(diff.changed[0].changes[0].new, json!("x"));
+    }
+
+    #[tokio::test]
+    async fn rejects_missing_keys() {
+        let ctx = SessionContext::new();
+        let old = frame(&ctx, vec![Some(1)], vec!["a"], "note");
+        let new = frame(&ctx, vec![Some(1)], vec!["a"], "email");
+
+        let error = RowDiff::compute(old, new, vec![String::from("note")], 1)
+            .await
+            .unwrap_err();
+        assert!(matches!(error, Error::InvalidArgument(_)));
+    }
+
+    #[test]
+    fn lists_changed_values() {
+        let row = json!({
+            "__old_id": 7,
+            "__new_id": 7,
+            "__old_name": "a",
+            "__new_name": "b",
+            "__old_amount": 10,
+            "__new_amount": 10,
+            "__new_email": "x@example.com"
+        });
+        let row = match row {
+            Value::Object(row) => row,
+            _ => unreachable!(),
+        };
+
+        let changed = ChangedRow::new(
+            row,
+            &[String::from("id")],
+            &[
+                String::from("name"),
+                String::from("amount"),
+                String::from("email"),
+            ],
+        );
+
+        assert_eq!(changed.key.get("id"), Some(&json!(7)));
+        assert_eq!(changed.changes.len(), 2);
+        assert_eq!(changed.changes[0].column, "name");
+        assert_eq!(changed.changes[1].old, Value::Null);
+        assert_eq!(changed.changes[1].new, json!("x@example.com"));
+    }
+}
@@ -0,0 +1,159 @@
+use std::sync::Arc;
+
+use datafusion::{
+    datasource::physical_plan::ParquetExec,
+    logical_expr::LogicalPlan,
+    physical_plan::{displayable, filter::FilterExec, metrics::MetricValue, ExecutionPlan},
+};
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct PlanMetric {
+    pub name: String,
+    /// summed over all partitions, times are in nanoseconds
+    pub value: usize,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct PlanNode {
+    pub operator: String,
+    /// the operator's one line description without the operator name
+    pub details: String,
+    /// the number of output partitions, physical plans only
+    pub partitions: Option<usize>,
+    pub columns: Vec<String>,
+    /// the predicates evaluated by the node, for scans these are the filters pushed down
+    pub filters: Vec<String>,
+    /// empty unless the plan was executed
+    pub metrics: Vec<PlanMetric>,
+    pub children: Vec<PlanNode>,
+}
+
+impl PlanNode {
+    pub fn from_logical(plan: &LogicalPlan) -> Self {
+        let (operator, details) = split_operator(&plan.display().to_string());
+
+        let filters = match plan {
+            LogicalPlan::TableScan(scan) => scan.filters.iter().map(|f| f.to_string()).collect(),
+            LogicalPlan::Filter(filter) => vec![filter.predicate.to_string()],
+            _ => Vec::new(),
+        };
+
+        Self {
+            operator,
+            details,
+            partitions: None,
+            columns: plan.schema().field_names(),
+            filters,
+            metrics: Vec::new(),
+            children: plan
+                .inputs()
+                .into_iter()
+                .map(PlanNode::from_logical)
+                .collect(),
+        }
+    }
+
+    pub fn from_physical(plan: &Arc<dyn ExecutionPlan>) -> Self {
+        let (operator, details) =
+            split_operator(&displayable(plan.as_ref()).one_line().to_string());
+
+        let mut filters = Vec::new();
+        if let Some(filter) = plan.as_any().downcast_ref::<FilterExec>() {
+            filters.push(filter.predicate().to_string());
+        }
+        if let Some(scan) = plan.as_any().downcast_ref::<ParquetExec>() {
+            if let Some(predicate) = scan.predicate() {
+                filters.push(predicate.to_string());
+            }
+        }
+
+        let metrics = match plan.metrics() {
+            Some(metrics) => metrics
+                .aggregate_by_name()
+                .sorted_for_display()
+                .iter()
+                .filter(|metric| {
+                    !matches!(
+                        metric.value(),
+                        MetricValue::StartTimestamp(_) | MetricValue::EndTimestamp(_)
+                    )
+                })
+                .map(|metric| PlanMetric {
+                    name: metric.value().name().to_string(),
+                    value: metric.value().as_usize(),
+                })
+                .collect(),
+            None => Vec::new(),
+        };
+
+        Self {
+            operator,
+            details,
+            partitions: Some(plan.output_partitioning().partition_count()),
+            columns: plan
+                .schema()
+                .fields()
+                .iter()
+                .map(|field| field.name().clone())
+                .collect(),
+            filters,
+            metrics,
+            children: plan
+                .children()
+                .iter()
+                .map(PlanNode::from_physical)
+                .collect(),
+        }
+    }
+}
+
+/// splits `Operator: details` as displayed by datafusion
+fn split_operator(line: &str) -> (String, String) {
+    match line.split_once(':') {
+        Some((operator, details)) => (operator.trim().to_string(), details.trim().to_string()),
+        None => (line.trim().to_string(), String::new()),
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct Explanation {
+    /// the optimized logical plan
+    pub logical: PlanNode,
+    pub physical: PlanNode,
+    /// true if the physical plan was executed and has metrics
+    pub analyzed: bool,
+}
+
+impl Explanation {
+    pub fn new(logical: &LogicalPlan, physical: &Arc<dyn ExecutionPlan>, analyzed: bool) -> Self {
+        Self {
+            logical: PlanNode::from_logical(logical),
+            physical: PlanNode::from_physical(physical),
+            analyzed,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_explain {
+    use super::*;
+
+    #[test]
+    fn splits_operators() {
+        assert_eq!(
+            split_operator("FilterExec: id@0 > 10"),
+            (String::from("FilterExec"), String::from("id@0 > 10"))
+        );
+        assert_eq!(
+            split_operator("TableScan: orders projection=[id, created_at]"),
+            (
+                String::from("TableScan"),
+                String::from("orders projection=[id, created_at]")
+            )
+        );
+        assert_eq!(
+            split_operator("EmptyRelation"),
+            (String::from("EmptyRelation"), String::new())
+        );
+    }
+}
@@ -0,0 +1,275 @@
+use std::{path::Path, sync::Arc};
+
+use datafusion::{
+    arrow::{
+        array::ArrayRef,
+        datatypes::{DataType, Field, Schema},
+        record_batch::{RecordBatch, RecordBatchOptions},
+    },
+    common::{DFSchema, ScalarValue},
+    datasource::empty::EmptyTable,
+    error::Result as DataFusionResult,
+    execution::context::SessionContext,
+    logical_expr::{cast, create_udf, ColumnarValue, LogicalPlan, ScalarUDF, Volatility},
+    physical_expr::{create_physical_expr, execution_props::ExecutionProps},
+    sql::sqlparser::{dialect::GenericDialect, tokenizer::Token, tokenizer::Tokenizer},
+};
+use tracing::debug;
+
+use crate::errors::Error;
+
+use super::{naming::sanitize, wasm};
+
+/// the table the parameters of a function are columns of while its body is planned
+const PARAMS_TABLE: &str = "params";
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct FunctionParam {
+    pub name: String,
+    pub data_type: DataType,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FunctionLanguage {
+    /// the body is a sql expression over the parameters
+    Sql,
+    /// the body is the file name of a webassembly module in the functions dir that exports a
+    /// function with the name of the definition
+    Wasm,
+}
+
+impl Default for FunctionLanguage {
+    fn default() -> Self {
+        Self::Sql
+    }
+}
+
+/// a scalar function whose body is a sql expression over its parameters, e.g.
+/// `upper(trim(code))` for a function with the parameter `code`, or a webassembly module
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct FunctionDefinition {
+    pub name: String,
+    pub params: Vec<FunctionParam>,
+    pub return_type: DataType,
+    pub body: String,
+    #[serde(default)]
+    pub language: FunctionLanguage,
+}
+
+impl FunctionDefinition {
+    fn validate(&self) -> Result<(), Error> {
+        let names = std::iter::once(&self.name).chain(self.params.iter().map(|param| &param.name));
+        for name in names {
+            if &sanitize(name) != name {
+                return Err(Error::InvalidFunction(format!(
+                    "`{}` is not a lowercase identifier",
+                    name
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// the names of the functions the body calls
+    pub fn calls(&self) -> Vec<String> {
+        if self.language == FunctionLanguage::Wasm {
+            return Vec::new();
+        }
+
+        let tokens = match Tokenizer::new(&GenericDialect {}, &self.body).tokenize() {
+            Ok(tokens) => tokens,
+            Err(_) => return Vec::new(),
+        };
+        let tokens: Vec<Token> = tokens
+            .into_iter()
+            .filter(|token| !matches!(token, Token::Whitespace(_)))
+            .collect();
+
+        let mut calls = Vec::new();
+        for pair in tokens.windows(2) {
+            if let [Token::Word(word), Token::LParen] = pair {
+                let name = word.value.to_lowercase();
+                if !calls.contains(&name) {
+                    calls.push(name);
+                }
+            }
+        }
+
+        calls
+    }
+
+    fn params_schema(&self) -> Schema {
+        Schema::new(
+            self.params
+                .iter()
+                .map(|param| Field::new(&param.name, param.data_type.clone(), true))
+                .collect::<Vec<Field>>(),
+        )
+    }
+
+    /// plans the body against the parameters, `functions` are the user functions the body may
+    /// call. wasm modules are loaded from `modules_dir`
+    pub async fn compile(
+        &self,
+        functions: &[ScalarUDF],
+        modules_dir: &Path,
+    ) -> Result<ScalarUDF, Error> {
+        self.validate()?;
+
+        if self.language == FunctionLanguage::Wasm {
+            return wasm::compile(self, modules_dir);
+        }
+
+        let ctx = SessionContext::new();
+        for function in functions {
+            ctx.register_udf(function.clone());
+        }
+
+        let schema = Arc::new(self.params_schema());
+        ctx.register_table(PARAMS_TABLE, Arc::new(EmptyTable::new(schema.clone())))?;
+
+        let statement = format!("SELECT {} FROM {}", self.body, PARAMS_TABLE);
+        let plan = ctx.state().create_logical_plan(&statement).await?;
+        let body = match plan {
+            LogicalPlan::Projection(projection) if projection.expr.len() == 1 => {
+                projection.expr[0].clone().unalias()
+            }
+            _ => {
+                return Err(Error::InvalidFunction(format!(
+                    "`{}` is not a single expression",
+                    self.body
+                )))
+            }
+        };
+        debug!(function = %self.name, body = %body, "planned function body");
+
+        let df_schema = DFSchema::try_from_qualified_schema(PARAMS_TABLE, &schema)?;
+        let expr = create_physical_expr(
+            &cast(body, self.return_type.clone()),
+            &df_schema,
+            &ExecutionProps::new(),
+        )?;
+
+        let input_types = self
+            .params
+            .iter()
+            .map(|param| param.data_type.clone())
+            .collect();
+
+        let fun = Arc::new(move |args: &[ColumnarValue]| {
+            let (columns, num_rows) = arguments(args)?;
+            let options = RecordBatchOptions::new().with_row_count(Some(num_rows));
+            let batch = RecordBatch::try_new_with_options(schema.clone(), columns, &options)?;
+
+            match expr.evaluate(&batch)? {
+                ColumnarValue::Array(array) if is_scalar(args) => Ok(ColumnarValue::Scalar(
+                    ScalarValue::try_from_array(&array, 0)?,
+                )),
+                result => Ok(result),
+            }
+        });
+
+        Ok(create_udf(
+            &self.name,
+            input_types,
+            Arc::new(self.return_type.clone()),
+            Volatility::Immutable,
+            fun,
+        ))
+    }
+}
+
+/// the arguments as arrays with the number of rows, scalars are repeated for every row
+pub(super) fn arguments(args: &[ColumnarValue]) -> DataFusionResult<(Vec<ArrayRef>, usize)> {
+    let num_rows = args
+        .iter()
+        .find_map(|arg| match arg {
+            ColumnarValue::Array(array) => Some(array.len()),
+            ColumnarValue::Scalar(_) => None,
+        })
+        .unwrap_or(1);
+
+    let columns = args
+        .iter()
+        .map(|arg| arg.clone().into_array(num_rows))
+        .collect::<DataFusionResult<Vec<_>>>()?;
+
+    Ok((columns, num_rows))
+}
+
+/// a function called with scalars only returns a scalar
+pub(super) fn is_scalar(args: &[ColumnarValue]) -> bool {
+    args.iter()
+        .all(|arg| matches!(arg, ColumnarValue::Scalar(_)))
+}
+
+/// the functions that have to be compiled again after `name` changed, they are compiled before
+/// the functions calling them and in id order otherwise. functions are `(id, definition)` pairs
+pub fn dependents(functions: &[(usize, &FunctionDefinition)], name: &str) -> Vec<usize> {
+    let mut functions: Vec<(usize, &str, Vec<String>)> = functions
+        .iter()
+        .filter(|(_, definition)| definition.name != name)
+        .map(|(id, definition)| (*id, definition.name.as_str(), definition.calls()))
+        .collect();
+    functions.sort_by_key(|(id, _, _)| *id);
+
+    let mut changed = vec![name.to_string()];
+    let mut pending: Vec<(usize, &str, Vec<String>)> = Vec::new();
+    loop {
+        let (affected, unaffected): (Vec<_>, Vec<_>) = functions
+            .into_iter()
+            .partition(|(_, _, calls)| calls.iter().any(|call| changed.contains(call)));
+        if affected.is_empty() {
+            break;
+        }
+        changed.extend(affected.iter().map(|(_, name, _)| name.to_string()));
+        pending.extend(affected);
+        functions = unaffected;
+    }
+    pending.sort_by_key(|(id, _, _)| *id);
+
+    let mut order = Vec::with_capacity(pending.len());
+    while !pending.is_empty() {
+        let is_pending = |call: &String| pending.iter().any(|(_, name, _)| name == call);
+        // functions that call each other are compiled in id order
+        let next = pending
+            .iter()
+            .position(|(_, _, calls)| !calls.iter().any(is_pending))
+            .unwrap_or(0);
+        order.push(pending.remove(next).0);
+    }
+
+    order
+}
+
+#[cfg(test)]
+mod test_functions {
+    use super::*;
+
+    fn definition(name: &str, body: &str) -> FunctionDefinition {
+        FunctionDefinition {
+            name: String::from(name),
+            params: Vec::new(),
+            return_type: DataType::Utf8,
+            body: String::from(body),
+            language: FunctionLanguage::Sql,
+        }
+    }
+
+    #[test]
+    fn orders_dependents_after_their_calls() {
+        let country = definition("country", "upper(trim(code))");
+        let region = definition("region", "concat(country(code), '-', area(code))");
+        let area = definition("area", "substr(country (code), 1, 2)");
+        let other = definition("other", "lower(code)");
+        let functions = vec![(5, &country), (3, &region), (4, &area), (6, &other)];
+
+        assert_eq!(
+            country.calls(),
+            vec![String::from("upper"), String::from("trim")]
+        );
+        assert_eq!(dependents(&functions, "country"), vec![4, 3]);
+        assert_eq!(dependents(&functions, "other"), Vec::<usize>::new());
+    }
+}
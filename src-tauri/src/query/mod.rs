@@ -1,74 +1,349 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::{hash_map::DefaultHasher, HashMap};
+use std::hash::{Hash, Hasher};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use datafusion::arrow::datatypes::Schema;
 use datafusion::common::{FileType, GetExt};
+use datafusion::dataframe::DataFrame;
 use datafusion::datasource::file_format::FileFormat;
 use datafusion::datasource::file_format::{parquet, parquet::ParquetFormat};
-use datafusion::datasource::listing::ListingOptions;
+use datafusion::datasource::listing::{
+    ListingOptions, ListingTable, ListingTableConfig, ListingTableUrl,
+};
+use datafusion::datasource::view::ViewTable;
 use datafusion::execution::context::{SessionContext, SessionState};
-use futures::lock::Mutex;
-use object_store::path::Path;
+use object_store::{path::Path, ObjectMeta};
 use tracing::{debug, info, warn};
 
-use futures::StreamExt;
+use futures::{lock::Mutex, StreamExt, TryStreamExt};
 
 use crate::errors::Error;
-use crate::state::store::{Connection, ObjectStore};
-
-use self::path_utils::dedup_paths;
-
+use crate::state::store::ObjectStore;
+
+use self::naming::{
+    sanitize, sources_table, store_schema, BufferTables, PrefixTable, TableName, UniqueNames,
+    DEFAULT_SCHEMA,
+};
+use self::params::QueryParams;
+pub use self::path_utils::{build_glob, PrefixPattern};
+use self::path_utils::{dedup_patterns, PrefixGlob, PrefixPatterns};
+use self::schemas::{
+    CachedSchema, FileLocation, SchemaCache, SchemaComparison, SchemaFailure, SchemaOverride,
+};
+use self::sources::SourcesTable;
+
+pub mod diff;
+pub mod explain;
+pub mod functions;
+pub mod naming;
+pub mod params;
 mod path_utils;
+pub mod results;
+pub mod schemas;
+pub mod sources;
+pub mod summary;
+mod wasm;
+
+/// the number of files read concurrently when inferring schemas
+const SCHEMA_INFERENCE_CONCURRENCY: usize = 8;
 
 #[derive(Debug, Clone)]
 pub struct FileSystemBuffer {
     store: ObjectStore,
-    prefixes: HashSet<Path>,
+    patterns: PrefixPatterns,
 }
 
 impl FileSystemBuffer {
-    pub fn new(store: ObjectStore, prefixes: &Vec<Path>) -> Self {
-        let paths = dedup_paths(prefixes);
+    pub fn new(store: ObjectStore, prefixes: &Vec<PrefixPattern>) -> Self {
+        let patterns = dedup_patterns(prefixes);
 
-        FileSystemBuffer {
-            store,
-            prefixes: paths,
-        }
+        FileSystemBuffer { store, patterns }
     }
 
     pub fn merge(&mut self, other: FileSystemBuffer) {
-        let paths = self.prefixes.union(&other.prefixes);
-        let paths: Vec<Path> = paths.into_iter().map(|path| path.clone()).collect();
+        let mut patterns = self.patterns.patterns();
+        patterns.extend(other.patterns.patterns());
 
-        let prefixes = dedup_paths(&paths);
-
-        self.prefixes = prefixes;
+        self.patterns = dedup_patterns(&patterns);
     }
 
     pub fn prefixes(&self) -> Vec<&Path> {
-        self.prefixes.iter().collect()
+        self.patterns.paths.iter().collect()
+    }
+
+    /// lists the files below a prefix, a prefix that is itself a file is returned as is
+    async fn list_prefix(&self, prefix: &Path) -> Result<Vec<ObjectMeta>, Error> {
+        let files: Vec<ObjectMeta> = self.store.client.list(Some(prefix)).try_collect().await?;
+
+        if files.is_empty() {
+            let file = self.store.client.head(prefix).await?;
+            return Ok(vec![file]);
+        }
+
+        Ok(files)
+    }
+
+    async fn list_glob(&self, glob: &PrefixGlob) -> Result<Vec<ObjectMeta>, Error> {
+        let matcher = glob.matcher()?;
+        // files that are also matched by a literal path or an earlier glob are read there
+        let earlier = self.patterns.earlier_globs(glob)?;
+        let files: Vec<ObjectMeta> = self
+            .store
+            .client
+            .list(Some(&glob.base))
+            .try_filter(|meta| {
+                let location = meta.location.as_ref();
+                futures::future::ready(
+                    matcher.is_match(location)
+                        && !earlier.is_match(location)
+                        && !self.patterns.covers(&meta.location),
+                )
+            })
+            .try_collect()
+            .await?;
+
+        Ok(files)
+    }
+
+    /// expands a path or glob into the files it matches, excluding any that match an exclusion
+    #[tracing::instrument(name = "expanding prefix pattern", skip(self), fields(
+        store = self.store.metadata.id
+    ))]
+    pub async fn expand(&self, pattern: &PrefixPattern) -> Result<Vec<ObjectMeta>, Error> {
+        let files = match pattern {
+            PrefixPattern::Path(path) => self.list_prefix(path).await?,
+            PrefixPattern::Glob(glob) => self.list_glob(glob).await?,
+            PrefixPattern::Exclude(_) => Vec::new(),
+        };
+
+        let exclusions = self.patterns.exclusion_matcher()?;
+        let files: Vec<ObjectMeta> = files
+            .into_iter()
+            .filter(|meta| !exclusions.is_match(meta.location.as_ref()))
+            .collect();
+
+        debug!(num_files = files.len(), "expanded prefix pattern");
+        Ok(files)
+    }
+
+    /// the literal paths and globs that each get a table, exclusions apply to all of them
+    fn table_patterns(&self) -> Vec<PrefixPattern> {
+        self.patterns
+            .patterns()
+            .into_iter()
+            .filter(|pattern| !matches!(pattern, PrefixPattern::Exclude(_)))
+            .collect()
+    }
+
+    /// the urls for the listing table of a pattern, a literal path is listed by datafusion while
+    /// globs and paths with exclusions are expanded into their files
+    async fn table_urls(
+        &self,
+        pattern: &PrefixPattern,
+        ext: &str,
+    ) -> Result<Vec<ListingTableUrl>, Error> {
+        if let PrefixPattern::Path(prefix) = pattern {
+            if !self.patterns.requires_expansion() {
+                let mut path = self.store.table_path(&prefix.to_string());
+
+                let is_file = path.ends_with(ext);
+                if !is_file {
+                    if !path.ends_with("/") {
+                        // datafusion requires a `/` at the end of the path if it is a directory
+                        path.push('/');
+                    }
+                }
+
+                return Ok(vec![ListingTableUrl::parse(path)?]);
+            }
+        }
+
+        let files = self.expand(pattern).await?;
+        let urls = files
+            .iter()
+            .map(|meta| ListingTableUrl::parse(self.store.table_path(meta.location.as_ref())))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(urls)
+    }
+
+    /// a copy whose store only sees the object versions at `as_of`
+    async fn pinned(&self, buffer: usize, as_of: i64) -> Result<Self, Error> {
+        let prefixes: Vec<Path> = self
+            .table_patterns()
+            .into_iter()
+            .filter_map(|pattern| match pattern {
+                PrefixPattern::Path(path) => Some(path),
+                PrefixPattern::Glob(glob) => Some(glob.base),
+                PrefixPattern::Exclude(_) => None,
+            })
+            .collect();
+
+        Ok(Self {
+            store: self.store.pin(buffer, as_of, &prefixes).await?,
+            patterns: self.patterns.clone(),
+        })
+    }
+
+    /// lists the files of the file system buffer that its tables read, files without the table
+    /// extension such as `_SUCCESS` markers are skipped
+    pub async fn list_files(&self) -> Result<Vec<ObjectMeta>, Error> {
+        let ext = FileType::PARQUET.get_ext();
+        let mut files = Vec::new();
+        for pattern in self.patterns.patterns() {
+            let expanded = self.expand(&pattern).await?;
+            files.extend(
+                expanded
+                    .into_iter()
+                    .filter(|meta| meta.location.as_ref().ends_with(&ext)),
+            );
+        }
+
+        files.sort_by(|a, b| a.location.cmp(&b.location));
+        files.dedup_by(|a, b| a.location == b.location);
+
+        Ok(files)
     }
 }
 
+/// the file systems of a buffer pinned to object versions, the versions are only listed again
+/// when the sources or the pinned time change
+#[derive(Debug, Clone)]
+pub struct CachedPin {
+    /// the sources fingerprint of the buffer, it includes the pinned time
+    fingerprint: String,
+    file_systems: HashMap<usize, FileSystemBuffer>,
+}
+
+pub type PinCache = Arc<Mutex<Option<CachedPin>>>;
+
 // A buffer represents a selection of paths to be queried and may be across multiple file systems
 #[derive(Debug, Clone)]
 pub struct Buffer {
     id: usize,
     name: String,
     file_systems: HashMap<usize, FileSystemBuffer>,
-    schema: Arc<Mutex<Option<Arc<Schema>>>>,
+    schema: SchemaCache,
+    schema_override: SchemaOverride,
+    /// the sql statement of a view buffer, view buffers do not have file systems
+    definition: Option<String>,
+    /// the objects are read as they were at this time, in milliseconds since the unix epoch
+    as_of: Option<i64>,
+    pins: PinCache,
+    listing_ttl: Option<Duration>,
 }
 
 impl Buffer {
-    pub fn new(id: &usize, name: &str, schema: Arc<Mutex<Option<Arc<Schema>>>>) -> Self {
+    pub fn new(
+        id: &usize,
+        name: &str,
+        schema: SchemaCache,
+        schema_override: SchemaOverride,
+    ) -> Self {
         Self {
             id: id.clone(),
             name: String::from(name),
             file_systems: HashMap::new(),
             schema: schema.clone(),
+            schema_override,
+            definition: None,
+            as_of: None,
+            pins: Arc::new(Mutex::new(None)),
+            listing_ttl: None,
+        }
+    }
+
+    pub fn with_definition(self, statement: String) -> Self {
+        Self {
+            definition: Some(statement),
+            ..self
         }
     }
 
+    pub fn definition(&self) -> Option<&str> {
+        self.definition.as_deref()
+    }
+
+    pub fn with_as_of(self, as_of: Option<i64>) -> Self {
+        Self { as_of, ..self }
+    }
+
+    pub fn with_pin_cache(self, pins: PinCache) -> Self {
+        Self { pins, ..self }
+    }
+
+    pub fn with_listing_ttl(self, listing_ttl: Option<Duration>) -> Self {
+        Self {
+            listing_ttl,
+            ..self
+        }
+    }
+
+    /// how long the file listings of the buffer are reused, the cache setting applies when `None`
+    pub fn listing_ttl(&self) -> Option<Duration> {
+        self.listing_ttl
+    }
+
+    /// the prefixes datafusion lists and caches the files of for the buffer tables with the url
+    /// of the store they are listed in, the store of a pinned buffer has its own url
+    pub async fn listing_prefixes(&self) -> Result<Vec<(String, Path)>, Error> {
+        let buffer = self.pinned().await?;
+        let prefixes = buffer
+            .file_systems
+            .values()
+            .flat_map(|file_system| {
+                let url = file_system.store.url();
+                file_system
+                    .table_patterns()
+                    .into_iter()
+                    .filter_map(move |pattern| match pattern {
+                        PrefixPattern::Path(path) => Some((url.clone(), path)),
+                        _ => None,
+                    })
+            })
+            .collect();
+
+        Ok(prefixes)
+    }
+
+    /// a copy of the buffer whose file systems only see the object versions it is pinned to
+    async fn pinned(&self) -> Result<Self, Error> {
+        let as_of = match self.as_of {
+            Some(as_of) => as_of,
+            None => return Ok(self.clone()),
+        };
+
+        let fingerprint = self.sources_fingerprint();
+        let mut cached = self.pins.lock().await;
+        if let Some(pin) = cached.as_ref() {
+            if pin.fingerprint == fingerprint {
+                return Ok(Self {
+                    file_systems: pin.file_systems.clone(),
+                    ..self.clone()
+                });
+            }
+        }
+
+        let mut file_systems = HashMap::new();
+        for (store, file_system) in self.file_systems.iter() {
+            file_systems.insert(*store, file_system.pinned(self.id, as_of).await?);
+        }
+        *cached = Some(CachedPin {
+            fingerprint,
+            file_systems: file_systems.clone(),
+        });
+
+        Ok(Self {
+            file_systems,
+            ..self.clone()
+        })
+    }
+
+    pub fn get_id(&self) -> usize {
+        self.id
+    }
+
     pub fn get_name(&self) -> &str {
         &self.name
     }
@@ -87,79 +362,157 @@ impl Buffer {
 }
 
 impl Buffer {
+    /// names the view for the buffer and a table for each path or glob in the buffer, the tables
+    /// are registered in the schema of their object store
+    pub fn tables(&self, catalog: &str, view: &str) -> BufferTables {
+        let prefixes = self
+            .prefix_tables(catalog)
+            .into_iter()
+            .map(|(_, _, table)| table)
+            .collect();
+
+        let sources = match self.definition {
+            Some(_) => None,
+            None => Some(TableName::new(
+                catalog,
+                DEFAULT_SCHEMA,
+                &sources_table(view),
+            )),
+        };
+
+        BufferTables {
+            buffer: self.id,
+            view: TableName::new(catalog, DEFAULT_SCHEMA, view),
+            sources,
+            prefixes,
+        }
+    }
+
+    fn prefix_tables(&self, catalog: &str) -> Vec<(&FileSystemBuffer, PrefixPattern, PrefixTable)> {
+        let mut file_systems: Vec<(&usize, &FileSystemBuffer)> = self.file_systems.iter().collect();
+        file_systems.sort_by_key(|(store, _)| **store);
+
+        let buffer_name = format!("{}_{}", sanitize(&self.name), self.id);
+        let mut names = UniqueNames::default();
+        let mut tables = Vec::new();
+
+        for (store, file_system) in file_systems {
+            let schema = store_schema(&file_system.store.metadata.name, *store);
+
+            let mut patterns = file_system.table_patterns();
+            patterns.sort_by_key(|pattern| pattern.to_string());
+
+            for pattern in patterns {
+                let table = names.unique(format!(
+                    "{}_{}",
+                    buffer_name,
+                    sanitize(&pattern.table_segment())
+                ));
+
+                let prefix_table = PrefixTable {
+                    store: *store,
+                    pattern: pattern.to_string(),
+                    name: TableName::new(catalog, &schema, &table),
+                };
+                tables.push((file_system, pattern, prefix_table));
+            }
+        }
+
+        tables
+    }
+
     #[tracing::instrument(
         name = "registering tables for buffer",
-        skip(self, table, ctx),
+        skip(self, view, ctx),
         fields(
-            table = %table,
+            view = %view,
             buffer_id = %self.id,
             buffer_name = %self.name
         )
     )]
-    pub async fn register(&self, table: &str, ctx: &SessionContext) -> Result<Vec<String>, Error> {
-        let mut tables = Vec::new();
+    pub async fn register(&self, view: &str, ctx: &SessionContext) -> Result<BufferTables, Error> {
         let state = ctx.state();
-        let schema = self.get_schema(&state).await?;
 
-        for (_, file_system) in self.file_systems.iter() {
-            let get_path: Box<dyn Fn(&str) -> String + Send + Sync> =
-                match file_system.store.connection.clone() {
-                    Connection::Local(_) => {
-                        let closure = |prefix: &str| -> String { format!("{}", prefix) };
-                        Box::new(closure)
-                    }
-                    Connection::Remote(connection) => {
-                        let bucket_name = connection.bucket;
-                        let base_url = format!("s3://{bucket_name}");
-                        let closure =
-                            move |prefix: &str| -> String { format!("{}/{}", base_url, prefix) };
-                        Box::new(closure)
-                    }
-                };
+        if let Some(statement) = &self.definition {
+            let catalog = state.config_options().catalog.default_catalog.clone();
+            let tables = self.tables(&catalog, view);
 
-            for prefix in file_system.prefixes.iter() {
-                let file_format = ParquetFormat::default().with_enable_pruning(Some(true));
-                let file_type = FileType::PARQUET;
-                let ext = file_type.get_ext();
-                let options =
-                    ListingOptions::new(Arc::new(file_format)).with_file_extension(ext.clone());
+            let plan = state.create_logical_plan(statement).await?;
+            let view_table = ViewTable::try_new(plan, Some(statement.clone()))?;
+            ctx.register_table(tables.view.table_reference(), Arc::new(view_table))?;
+            info!(view = %tables.view.reference, "created view from statement");
 
-                let mut path = get_path(&prefix.to_string());
+            return Ok(tables);
+        }
 
-                let is_file = path.ends_with(&ext);
-                if !is_file {
-                    if !path.ends_with("/") {
-                        // datafusion requires a `/` at the end of the path if it is a directory
-                        path.push('/');
-                    }
-                }
+        self.pinned().await?.register_files(view, ctx).await
+    }
+
+    async fn register_files(
+        &self,
+        view: &str,
+        ctx: &SessionContext,
+    ) -> Result<BufferTables, Error> {
+        let state = ctx.state();
+        for file_system in self.file_systems.values() {
+            file_system.store.register_versions(ctx)?;
+        }
 
-                let file_system_name = file_system.store.metadata.name.clone();
-                let table_prefix = prefix.to_string().replace("/", ".");
-                let table = format!("{file_system_name}.{table_prefix}");
-                ctx.register_listing_table(&table, &path, options, Some(schema.clone()), None)
-                    .await?;
-                tables.push(table);
+        let schema = self.get_schema(&state).await?;
+        let catalog = state.config_options().catalog.default_catalog.clone();
+
+        let file_format = ParquetFormat::default().with_enable_pruning(Some(true));
+        let file_type = FileType::PARQUET;
+        let ext = file_type.get_ext();
+        let options = ListingOptions::new(Arc::new(file_format)).with_file_extension(ext.clone());
+
+        let mut tables = self.tables(&catalog, view);
+        tables.prefixes.clear();
+
+        let mut view_df: Option<DataFrame> = None;
+        for (file_system, pattern, prefix_table) in self.prefix_tables(&catalog) {
+            let urls = file_system.table_urls(&pattern, &ext).await?;
+            if urls.is_empty() {
+                warn!(pattern = %pattern, "no files match prefix pattern");
+                continue;
             }
+
+            let config = ListingTableConfig::new_with_multi_paths(urls)
+                .with_listing_options(options.clone())
+                .with_schema(schema.clone());
+            let listing_table = ListingTable::try_new(config)?;
+
+            let table_reference = prefix_table.name.table_reference();
+            ctx.register_table(table_reference.clone(), Arc::new(listing_table))?;
+            debug!(table = %prefix_table.name.reference, "registered table");
+
+            let df = ctx.table(table_reference).await?;
+            view_df = match view_df {
+                Some(view_df) => Some(view_df.union(df)?),
+                None => Some(df),
+            };
+            tables.prefixes.push(prefix_table);
         }
-        let table_names: Vec<String> = tables
-            .iter()
-            .map(|name| format!("SELECT * FROM '{}'", name))
-            .collect();
-        let table_names = table_names.join(" UNION ALL ");
 
-        let create_table = format!("CREATE VIEW '{}' AS {};", table, table_names);
-        debug!(table = %table, create_table = %create_table, "creating view from tables",);
-        let create_table_result = ctx.sql(&create_table).await;
+        let view_df = view_df.ok_or(Error::NotFound(format!(
+            "no tables to register for buffer {}",
+            self.id
+        )))?;
+        ctx.register_table(tables.view.table_reference(), view_df.into_view())?;
 
-        match create_table_result {
-            Ok(_) => {
-                info!(table = %table, "created view table");
+        let file_systems = self.file_systems.values().cloned().collect();
+        match SourcesTable::new(schema.clone(), options, file_systems) {
+            Some(sources) => {
+                if let Some(name) = &tables.sources {
+                    ctx.register_table(name.table_reference(), Arc::new(sources))?;
+                }
             }
-            Err(e) => {
-                warn!(?e, table = %table, "failed to create view table");
+            None => {
+                warn!("files have columns named like the source columns, not registering sources");
+                tables.sources = None;
             }
-        };
+        }
+        info!(view = %tables.view.reference, "created view table");
 
         Ok(tables)
     }
@@ -173,15 +526,60 @@ impl Buffer {
         )
     )]
     pub async fn get_schema(&self, session_state: &SessionState) -> Result<Arc<Schema>, Error> {
-        let schema = {
-            let schema = self.schema.lock().await;
-            schema.clone()
-        };
+        if let Some(statement) = &self.definition {
+            let plan = session_state.create_logical_plan(statement).await?;
+            let schema = Schema::from(plan.schema().as_ref());
+            return Ok(Arc::new(schema));
+        }
 
-        if let Some(schema) = schema {
+        if let Some(schema) = &self.schema_override.schema {
+            debug!("using schema override");
             return Ok(schema.clone());
         }
 
+        let schema = self.pinned().await?.infer_schema(session_state).await?;
+
+        Ok(self.schema_override.apply(schema))
+    }
+
+    async fn infer_schema(&self, session_state: &SessionState) -> Result<Arc<Schema>, Error> {
+        let cached = {
+            let cached = self.schema.lock().await;
+            cached.clone()
+        };
+
+        // the files are only listed again once the listing ttl of the buffer has passed, without
+        // a ttl they are listed every time
+        let ttl = self.listing_ttl.unwrap_or(Duration::ZERO);
+        if let Some(cached) = &cached {
+            if cached.is_current(&self.sources_fingerprint(), ttl) {
+                return Ok(cached.schema.clone());
+            }
+        }
+
+        let fingerprint = self.fingerprint().await?;
+        match cached {
+            Some(cached) if cached.fingerprint == fingerprint => {
+                self.mark_schema_checked().await;
+                return Ok(cached.schema.clone());
+            }
+            Some(cached) => {
+                info!(
+                    cached = %cached.fingerprint,
+                    current = %fingerprint,
+                    "buffer files changed, invalidating cached schema"
+                );
+            }
+            None => {}
+        }
+
+        let file_system = self.get_sample_file_system()?;
+        if file_system.patterns.requires_expansion() {
+            return self
+                .infer_schema_from_patterns(file_system, session_state, &fingerprint)
+                .await;
+        }
+
         let (file_system, prefix) = self.get_sample_prefix()?;
         let mut stream = file_system.store.client.list(Some(prefix));
 
@@ -198,10 +596,7 @@ impl Buffer {
                         Ok(schema) => {
                             info!(item=?item, "inferred schema for object");
 
-                            {
-                                let mut lock = self.schema.lock().await;
-                                *lock = Some(schema.clone());
-                            }
+                            self.cache_schema(&schema, &fingerprint).await;
                             return Ok(schema);
                         }
                         Err(e) => {
@@ -241,10 +636,7 @@ impl Buffer {
             Ok(schema) => {
                 info!(item=?item, "inferred schema for object");
 
-                {
-                    let mut lock = self.schema.lock().await;
-                    *lock = Some(schema.clone());
-                }
+                self.cache_schema(&schema, &fingerprint).await;
                 return Ok(schema);
             }
             Err(e) => {
@@ -264,17 +656,260 @@ impl Buffer {
         )))
     }
 
+    async fn infer_schema_from_patterns(
+        &self,
+        file_system: &FileSystemBuffer,
+        session_state: &SessionState,
+        fingerprint: &str,
+    ) -> Result<Arc<Schema>, Error> {
+        let patterns = file_system.patterns.patterns();
+        for pattern in patterns.iter() {
+            let files = file_system.expand(pattern).await?;
+            let item = match files.first() {
+                Some(item) => item,
+                None => continue,
+            };
+
+            let format = parquet::ParquetFormat::default();
+            let objects = &[item.clone()];
+            let schema = format
+                .infer_schema(session_state, &file_system.store.client, objects)
+                .await;
+
+            match schema {
+                Ok(schema) => {
+                    info!(item=?item, "inferred schema for object");
+
+                    self.cache_schema(&schema, fingerprint).await;
+                    return Ok(schema);
+                }
+                Err(e) => {
+                    warn!(
+                        ?e,
+                        store = file_system.store.metadata.id,
+                        pattern = %pattern,
+                        item = ?item,
+                        "failed to infer schema for object"
+                    );
+                }
+            }
+        }
+
+        Err(Error::NotFound(format!(
+            "failed to find schema for file system {} buffer {}",
+            file_system.store.metadata.id, self.id
+        )))
+    }
+
+    /// infers the schema of each file in the buffer, or of an evenly spaced sample of them, and
+    /// groups the files by schema
+    #[tracing::instrument(
+        name = "comparing schemas for buffer",
+        skip(self, session_state),
+        fields(
+            buffer_id = %self.id,
+            buffer_name = %self.name
+        )
+    )]
+    pub async fn compare_schemas(
+        &self,
+        session_state: &SessionState,
+        sample: Option<usize>,
+    ) -> Result<SchemaComparison, Error> {
+        let buffer = self.pinned().await?;
+        let mut files = Vec::new();
+        for file_system in buffer.file_systems.values() {
+            for meta in file_system.list_files().await? {
+                files.push((file_system, meta));
+            }
+        }
+
+        let num_files = files.len();
+        let step = match sample {
+            Some(sample) if sample > 0 && sample < num_files => (num_files + sample - 1) / sample,
+            _ => 1,
+        };
+
+        let results: Vec<_> = futures::stream::iter(files.into_iter().step_by(step))
+            .map(|(file_system, meta)| async move {
+                let file = FileLocation {
+                    store: file_system.store.metadata.id,
+                    location: meta.location.to_string(),
+                };
+                let format = parquet::ParquetFormat::default();
+                let schema = format
+                    .infer_schema(session_state, &file_system.store.client, &[meta])
+                    .await;
+                (file, schema)
+            })
+            .buffer_unordered(SCHEMA_INFERENCE_CONCURRENCY)
+            .collect()
+            .await;
+
+        let mut schemas = Vec::new();
+        let mut failures = Vec::new();
+        for (file, schema) in results {
+            match schema {
+                Ok(schema) => schemas.push((file, schema)),
+                Err(e) => {
+                    warn!(?e, file = ?file, "failed to infer schema for object");
+                    failures.push(SchemaFailure {
+                        file,
+                        error: e.to_string(),
+                    });
+                }
+            }
+        }
+
+        let comparison = SchemaComparison::new(num_files, schemas, failures);
+        info!(
+            num_files,
+            num_sampled = comparison.num_sampled,
+            num_groups = comparison.groups.len(),
+            "compared schemas"
+        );
+
+        Ok(comparison)
+    }
+
+    /// identifies the stores, prefixes and pinned time of the buffer
+    fn sources_fingerprint(&self) -> String {
+        let mut sources: Vec<String> = self
+            .file_systems
+            .iter()
+            .flat_map(|(store, file_system)| {
+                file_system
+                    .patterns
+                    .patterns()
+                    .into_iter()
+                    .map(move |pattern| format!("{}:{}", store, pattern))
+            })
+            .collect();
+        sources.sort();
+
+        match self.as_of {
+            Some(as_of) => format!("{}@{}", sources.join(";"), as_of),
+            None => sources.join(";"),
+        }
+    }
+
+    /// identifies the sources of the buffer and the files listed from them, a cached schema with a
+    /// different fingerprint is stale. new, removed or rewritten files change the fingerprint
+    pub async fn fingerprint(&self) -> Result<String, Error> {
+        let mut files: Vec<String> = self
+            .list_files()
+            .await?
+            .into_iter()
+            .map(|(store, meta)| {
+                format!(
+                    "{}:{}:{}:{}",
+                    store.metadata.id,
+                    meta.location,
+                    meta.size,
+                    meta.e_tag
+                        .unwrap_or_else(|| meta.last_modified.timestamp_millis().to_string())
+                )
+            })
+            .collect();
+        files.sort();
+
+        let mut hasher = DefaultHasher::new();
+        files.hash(&mut hasher);
+
+        Ok(format!(
+            "{}#{}:{:x}",
+            self.sources_fingerprint(),
+            files.len(),
+            hasher.finish()
+        ))
+    }
+
+    /// changes whenever the prefixes, stores or schema override of the buffer change, the tables
+    /// of a buffer have to be registered again when it does
+    pub fn registration_key(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.sources_fingerprint().hash(&mut hasher);
+
+        let mut stores: Vec<&ObjectStore> = self
+            .file_systems
+            .values()
+            .map(|file_system| &file_system.store)
+            .collect();
+        stores.sort_by_key(|store| store.metadata.id);
+        for store in stores {
+            store.metadata.name.hash(&mut hasher);
+            serde_json::to_string(&store.connection)
+                .unwrap_or_default()
+                .hash(&mut hasher);
+        }
+
+        serde_json::to_string(&self.schema_override)
+            .unwrap_or_default()
+            .hash(&mut hasher);
+        self.definition.hash(&mut hasher);
+
+        hasher.finish()
+    }
+
+    async fn cache_schema(&self, schema: &Arc<Schema>, fingerprint: &str) {
+        let mut lock = self.schema.lock().await;
+        *lock = Some(CachedSchema {
+            schema: schema.clone(),
+            fingerprint: fingerprint.to_string(),
+            persisted: false,
+            checked_at: Some(Instant::now()),
+        });
+    }
+
+    /// records that the files of the cached schema were listed and have not changed
+    async fn mark_schema_checked(&self) {
+        let mut lock = self.schema.lock().await;
+        if let Some(cached) = lock.as_mut() {
+            cached.checked_at = Some(Instant::now());
+        }
+    }
+
+    /// returns the cached schema if it was inferred since the buffer was loaded and has not been
+    /// saved yet
+    pub async fn unpersisted_schema(&self) -> Option<CachedSchema> {
+        let lock = self.schema.lock().await;
+        lock.clone().filter(|cached| !cached.persisted)
+    }
+
+    /// lists the files of every file system in the buffer with the store they are in, the store
+    /// of a pinned buffer reads the pinned versions
+    pub async fn list_files(&self) -> Result<Vec<(ObjectStore, ObjectMeta)>, Error> {
+        let buffer = self.pinned().await?;
+        let mut files = Vec::new();
+        for file_system in buffer.file_systems.values() {
+            for meta in file_system.list_files().await? {
+                files.push((file_system.store.clone(), meta));
+            }
+        }
+
+        Ok(files)
+    }
+
+    fn get_sample_file_system(&self) -> Result<&FileSystemBuffer, Error> {
+        let file_system = self
+            .file_systems
+            .values()
+            .next()
+            .ok_or(Error::NotFound(format!(
+                "no file systems in buffer {}",
+                self.id
+            )))?;
+
+        Ok(file_system)
+    }
+
     fn get_sample_prefix(&self) -> Result<(&FileSystemBuffer, &Path), Error> {
-        let file_systems: Vec<(&usize, &FileSystemBuffer)> = self.file_systems.iter().collect();
-        let (file_system_id, file_system) = file_systems.get(0).ok_or(Error::NotFound(format!(
-            "no file systems in buffer {}",
-            self.id
-        )))?;
+        let file_system = self.get_sample_file_system()?;
 
         let prefixes = file_system.prefixes();
         let prefix = prefixes.get(0).ok_or(Error::NotFound(format!(
             "no prefixes for file system {} in buffer {}",
-            file_system_id, self.id
+            file_system.store.metadata.id, self.id
         )))?;
 
         Ok((file_system, prefix))
@@ -284,5 +919,51 @@ impl Buffer {
 #[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
 pub struct Query {
     pub statement: String,
-    pub buffer: usize,
+    /// registered in addition to the buffers referenced by name in the statement
+    pub buffer: Option<usize>,
+    /// the maximum number of rows returned, the row limit setting is used when not set
+    #[serde(default)]
+    pub limit: Option<usize>,
+    /// bound to the placeholders of the statement when it is planned
+    #[serde(default)]
+    pub params: Option<QueryParams>,
+}
+
+#[cfg(test)]
+mod test_file_system_buffer {
+    use crate::state::store::{Connection, LocalConnection, Metadata, ObjectStoreKind};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn lists_only_table_files() {
+        let dir = std::env::temp_dir().join(format!("file-fusion-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        for file in ["a.parquet", "b.parquet", "_SUCCESS", ".a.parquet.crc"] {
+            std::fs::write(dir.join(file), "x").unwrap();
+        }
+        let store = ObjectStore::new(
+            Metadata {
+                id: 0,
+                name: String::from("Local"),
+                prefix: String::new(),
+                kind: ObjectStoreKind::Local,
+            },
+            Connection::Local(LocalConnection {}),
+        )
+        .unwrap();
+        let prefix = dir.to_str().unwrap().trim_start_matches('/');
+        let file_system =
+            FileSystemBuffer::new(store, &vec![PrefixPattern::parse(prefix).unwrap()]);
+
+        let files = file_system.list_files().await.unwrap();
+        let names: Vec<&str> = files
+            .iter()
+            .map(|meta| meta.location.filename().unwrap())
+            .collect();
+
+        assert_eq!(names, vec!["a.parquet", "b.parquet"]);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
 }
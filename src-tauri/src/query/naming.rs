@@ -0,0 +1,237 @@
+use std::collections::HashMap;
+
+use datafusion::common::{OwnedTableReference, TableReference};
+
+/// the schema that buffer views are registered in
+pub const DEFAULT_SCHEMA: &str = "public";
+
+/// maps a name to a lowercase identifier that only contains `[a-z0-9_]` so it can be referenced in
+/// sql without quoting in most cases
+pub fn sanitize(name: &str) -> String {
+    let mut sanitized = String::with_capacity(name.len());
+    for c in name.chars() {
+        if c.is_ascii_alphanumeric() {
+            sanitized.push(c.to_ascii_lowercase());
+        } else if !sanitized.ends_with('_') {
+            sanitized.push('_');
+        }
+    }
+
+    let sanitized = sanitized.trim_matches('_');
+    if sanitized.is_empty() {
+        return String::from("_");
+    }
+
+    if sanitized.starts_with(|c: char| c.is_ascii_digit()) {
+        return format!("_{}", sanitized);
+    }
+
+    sanitized.to_string()
+}
+
+/// quotes an identifier so it is used as is, without being normalized or split on `.`
+pub fn quote(identifier: &str) -> String {
+    format!("\"{}\"", identifier.replace('"', "\"\""))
+}
+
+/// the name of the schema each object store's tables are registered in
+pub fn store_schema(name: &str, id: usize) -> String {
+    format!("{}_{}", sanitize(name), id)
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct TableName {
+    pub catalog: String,
+    pub schema: String,
+    pub table: String,
+    /// the quoted name to use in sql
+    pub reference: String,
+}
+
+impl TableName {
+    pub fn new(catalog: &str, schema: &str, table: &str) -> Self {
+        let reference = if schema == DEFAULT_SCHEMA {
+            quote(table)
+        } else {
+            format!("{}.{}", quote(schema), quote(table))
+        };
+
+        Self {
+            catalog: catalog.to_string(),
+            schema: schema.to_string(),
+            table: table.to_string(),
+            reference,
+        }
+    }
+
+    /// true if the reference resolves to this table, a reference without a schema resolves to the
+    /// default schema
+    pub fn matches(&self, reference: &TableReference) -> bool {
+        let schema = reference.schema().unwrap_or(DEFAULT_SCHEMA);
+        let catalog_matches = reference
+            .catalog()
+            .map_or(true, |catalog| catalog == self.catalog);
+
+        catalog_matches && schema == self.schema && reference.table() == self.table
+    }
+
+    pub fn table_reference(&self) -> OwnedTableReference {
+        TableReference::full(
+            self.catalog.clone(),
+            self.schema.clone(),
+            self.table.clone(),
+        )
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct PrefixTable {
+    pub store: usize,
+    pub pattern: String,
+    pub name: TableName,
+}
+
+/// the name of the table with the file metadata columns of a buffer's view
+pub fn sources_table(view: &str) -> String {
+    format!("{}_sources", view)
+}
+
+/// the view names of buffers by id. buffers are referenced by name in sql, a buffer that shares
+/// its name with an older buffer or whose name or sources table name is the other's sources table
+/// or name gets its id appended
+pub fn view_names<'a>(
+    buffers: impl IntoIterator<Item = (usize, &'a str)>,
+) -> HashMap<usize, String> {
+    let buffers: Vec<(usize, &str)> = buffers.into_iter().collect();
+
+    // the oldest buffer with each name or sources table name
+    let mut oldest: HashMap<String, usize> = HashMap::new();
+    for (id, name) in buffers.iter() {
+        for name in [name.to_string(), sources_table(name)] {
+            let oldest_id = oldest.entry(name).or_insert(*id);
+            *oldest_id = (*oldest_id).min(*id);
+        }
+    }
+
+    buffers
+        .iter()
+        .map(|(id, name)| {
+            let is_duplicate = [name.to_string(), sources_table(name)]
+                .iter()
+                .any(|name| oldest.get(name).map_or(false, |oldest_id| oldest_id < id));
+
+            let view = if is_duplicate {
+                format!("{}_{}", name, id)
+            } else {
+                name.to_string()
+            };
+            (*id, view)
+        })
+        .collect()
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct BufferTables {
+    pub buffer: usize,
+    /// the union of all prefix tables
+    pub view: TableName,
+    /// the view's rows with the file each row was read from, not registered for view buffers
+    pub sources: Option<TableName>,
+    pub prefixes: Vec<PrefixTable>,
+}
+
+impl BufferTables {
+    pub fn names(&self) -> Vec<&TableName> {
+        std::iter::once(&self.view)
+            .chain(self.sources.iter())
+            .chain(self.prefixes.iter().map(|table| &table.name))
+            .collect()
+    }
+
+    pub fn matches(&self, reference: &TableReference) -> bool {
+        self.names().iter().any(|name| name.matches(reference))
+    }
+}
+
+/// generates table names that are unique within a schema by appending a counter to duplicates
+#[derive(Debug, Default)]
+pub struct UniqueNames {
+    names: Vec<String>,
+}
+
+impl UniqueNames {
+    pub fn unique(&mut self, name: String) -> String {
+        let mut unique = name.clone();
+        let mut counter = 1;
+        while self.names.contains(&unique) {
+            counter += 1;
+            unique = format!("{}_{}", name, counter);
+        }
+
+        self.names.push(unique.clone());
+        unique
+    }
+}
+
+#[cfg(test)]
+mod test_naming {
+    use super::*;
+
+    #[test]
+    fn sanitizes_names() {
+        assert_eq!(sanitize("My Bucket"), "my_bucket");
+        assert_eq!(sanitize("/Users/me/data.v2/"), "users_me_data_v2");
+        assert_eq!(sanitize("2024-01"), "_2024_01");
+        assert_eq!(sanitize("***"), "_");
+    }
+
+    #[test]
+    fn quotes_identifiers() {
+        assert_eq!(quote("orders"), "\"orders\"");
+        assert_eq!(quote("my \"orders\""), "\"my \"\"orders\"\"\"");
+
+        let name = TableName::new("datafusion", "local_1", "orders_3_events");
+        assert_eq!(name.reference, "\"local_1\".\"orders_3_events\"");
+
+        let name = TableName::new("datafusion", DEFAULT_SCHEMA, "my orders");
+        assert_eq!(name.reference, "\"my orders\"");
+    }
+
+    #[test]
+    fn matches_references() {
+        let view = TableName::new("datafusion", DEFAULT_SCHEMA, "orders");
+        assert!(view.matches(&TableReference::bare("orders")));
+        assert!(view.matches(&TableReference::partial("public", "orders")));
+        assert!(!view.matches(&TableReference::bare("customers")));
+
+        let table = TableName::new("datafusion", "local_1", "orders_3_events");
+        assert!(table.matches(&TableReference::partial("local_1", "orders_3_events")));
+        assert!(!table.matches(&TableReference::bare("orders_3_events")));
+    }
+
+    #[test]
+    fn appends_ids_to_duplicate_view_names() {
+        let names = view_names(vec![
+            (1, "orders"),
+            (2, "orders"),
+            (3, "orders_sources"),
+            (4, "events_sources"),
+            (5, "events"),
+        ]);
+
+        assert_eq!(names[&1], "orders");
+        assert_eq!(names[&2], "orders_2");
+        assert_eq!(names[&3], "orders_sources_3");
+        assert_eq!(names[&4], "events_sources");
+        assert_eq!(names[&5], "events_5");
+    }
+
+    #[test]
+    fn dedups_names() {
+        let mut names = UniqueNames::default();
+
+        assert_eq!(names.unique(String::from("events")), "events");
+        assert_eq!(names.unique(String::from("events")), "events_2");
+        assert_eq!(names.unique(String::from("events")), "events_3");
+    }
+}
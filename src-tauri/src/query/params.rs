@@ -0,0 +1,133 @@
+use std::collections::HashMap;
+
+use datafusion::{
+    arrow::datatypes::{DataType, TimeUnit},
+    common::{ParamValues, ScalarValue},
+};
+
+use crate::errors::Error;
+
+/// a typed value bound to a placeholder, dates, timestamps and decimals are parsed from strings
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", content = "value")]
+pub enum ParamValue {
+    Null,
+    Boolean(bool),
+    Int64(i64),
+    UInt64(u64),
+    Float64(f64),
+    Utf8(String),
+    /// `YYYY-MM-DD`
+    Date(String),
+    /// an RFC 3339 timestamp, stored with microsecond precision
+    Timestamp(String),
+    Decimal {
+        value: String,
+        precision: u8,
+        scale: i8,
+    },
+}
+
+impl ParamValue {
+    pub fn to_scalar(&self) -> Result<ScalarValue, Error> {
+        let scalar = match self {
+            ParamValue::Null => ScalarValue::Null,
+            ParamValue::Boolean(value) => ScalarValue::Boolean(Some(*value)),
+            ParamValue::Int64(value) => ScalarValue::Int64(Some(*value)),
+            ParamValue::UInt64(value) => ScalarValue::UInt64(Some(*value)),
+            ParamValue::Float64(value) => ScalarValue::Float64(Some(*value)),
+            ParamValue::Utf8(value) => ScalarValue::Utf8(Some(value.clone())),
+            ParamValue::Date(value) => {
+                ScalarValue::try_from_string(value.clone(), &DataType::Date32)?
+            }
+            ParamValue::Timestamp(value) => ScalarValue::try_from_string(
+                value.clone(),
+                &DataType::Timestamp(TimeUnit::Microsecond, None),
+            )?,
+            ParamValue::Decimal {
+                value,
+                precision,
+                scale,
+            } => ScalarValue::try_from_string(
+                value.clone(),
+                &DataType::Decimal128(*precision, *scale),
+            )?,
+        };
+
+        Ok(scalar)
+    }
+}
+
+/// values for the placeholders of a statement, `$1` is the first positional value and `$name` or
+/// `:name` is the named value `name`
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq)]
+pub enum QueryParams {
+    Positional(Vec<ParamValue>),
+    Named(HashMap<String, ParamValue>),
+}
+
+impl QueryParams {
+    pub fn to_param_values(&self) -> Result<ParamValues, Error> {
+        let values = match self {
+            QueryParams::Positional(values) => ParamValues::List(
+                values
+                    .iter()
+                    .map(ParamValue::to_scalar)
+                    .collect::<Result<_, _>>()?,
+            ),
+            QueryParams::Named(values) => ParamValues::Map(
+                values
+                    .iter()
+                    .map(|(name, value)| {
+                        let name = name.trim_start_matches(|c| c == '$' || c == ':');
+                        Ok((name.to_string(), value.to_scalar()?))
+                    })
+                    .collect::<Result<_, Error>>()?,
+            ),
+        };
+
+        Ok(values)
+    }
+}
+
+#[cfg(test)]
+mod test_params {
+    use super::*;
+
+    #[test]
+    fn converts_typed_values() {
+        let date = ParamValue::Date(String::from("2024-01-02"));
+        assert_eq!(date.to_scalar().unwrap(), ScalarValue::Date32(Some(19724)));
+
+        let decimal = ParamValue::Decimal {
+            value: String::from("12.50"),
+            precision: 10,
+            scale: 2,
+        };
+        assert_eq!(
+            decimal.to_scalar().unwrap(),
+            ScalarValue::Decimal128(Some(1250), 10, 2)
+        );
+
+        assert!(ParamValue::Date(String::from("yesterday"))
+            .to_scalar()
+            .is_err());
+    }
+
+    #[test]
+    fn strips_placeholder_prefixes() {
+        let params: QueryParams =
+            serde_json::from_str(r#"{"Named": {":customer_id": {"type": "Int64", "value": 42}}}"#)
+                .unwrap();
+
+        match params.to_param_values().unwrap() {
+            ParamValues::Map(values) => {
+                assert_eq!(
+                    values.get("customer_id"),
+                    Some(&ScalarValue::Int64(Some(42)))
+                )
+            }
+            ParamValues::List(_) => panic!("expected named values"),
+        }
+    }
+}
@@ -1,7 +1,174 @@
-use std::collections::HashSet;
+use std::{collections::HashSet, fmt};
 
+use globset::{Glob, GlobBuilder, GlobSet, GlobSetBuilder};
 use object_store::path::Path;
 
+use crate::errors::Error;
+
+const GLOB_CHARS: &[char] = &['*', '?', '[', '{'];
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PrefixGlob {
+    /// the longest literal prefix of the pattern, listing starts here
+    pub base: Path,
+    pub pattern: String,
+}
+
+impl PrefixGlob {
+    pub fn matcher(&self) -> Result<GlobSet, Error> {
+        build_glob_set([self.pattern.as_str()])
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum PrefixPattern {
+    Path(Path),
+    Glob(PrefixGlob),
+    Exclude(String),
+}
+
+impl PrefixPattern {
+    /// parses a buffer prefix, patterns starting with `!` are exclusions and patterns containing
+    /// any of `*?[{` are globs, everything else is a literal path
+    pub fn parse(prefix: &str) -> Result<Self, Error> {
+        if let Some(exclusion) = prefix.strip_prefix('!') {
+            let exclusion = normalize_pattern(exclusion);
+            build_glob(&exclusion)?;
+            return Ok(Self::Exclude(exclusion));
+        }
+
+        if !prefix.contains(GLOB_CHARS) {
+            return Ok(Self::Path(Path::parse(prefix)?));
+        }
+
+        let pattern = normalize_pattern(prefix);
+        build_glob(&pattern)?;
+        let base: Vec<&str> = pattern
+            .split('/')
+            .take_while(|segment| !segment.contains(GLOB_CHARS))
+            .collect();
+        let base = Path::parse(base.join("/"))?;
+
+        Ok(Self::Glob(PrefixGlob { base, pattern }))
+    }
+}
+
+impl PrefixPattern {
+    /// the last literal segment of the pattern, used to name the table for the pattern
+    pub fn table_segment(&self) -> String {
+        let path = match self {
+            Self::Path(path) => path,
+            Self::Glob(glob) => &glob.base,
+            Self::Exclude(_) => return String::from("exclude"),
+        };
+
+        match path.parts().last() {
+            Some(part) => part.as_ref().to_string(),
+            None => String::from("root"),
+        }
+    }
+}
+
+impl fmt::Display for PrefixPattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Path(path) => write!(f, "{}", path),
+            Self::Glob(glob) => write!(f, "{}", glob.pattern),
+            Self::Exclude(exclusion) => write!(f, "!{}", exclusion),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct PrefixPatterns {
+    pub paths: HashSet<Path>,
+    pub globs: HashSet<PrefixGlob>,
+    pub exclusions: HashSet<String>,
+}
+
+impl PrefixPatterns {
+    pub fn patterns(&self) -> Vec<PrefixPattern> {
+        let paths = self.paths.iter().cloned().map(PrefixPattern::Path);
+        let globs = self.globs.iter().cloned().map(PrefixPattern::Glob);
+        let exclusions = self.exclusions.iter().cloned().map(PrefixPattern::Exclude);
+
+        paths.chain(globs).chain(exclusions).collect()
+    }
+
+    /// literal paths without exclusions can be handed to datafusion as is, everything else has to
+    /// be expanded into a list of files
+    pub fn requires_expansion(&self) -> bool {
+        !self.globs.is_empty() || !self.exclusions.is_empty()
+    }
+
+    pub fn exclusion_matcher(&self) -> Result<GlobSet, Error> {
+        build_glob_set(self.exclusions.iter().map(|exclusion| exclusion.as_str()))
+    }
+
+    /// the globs ordered before `glob`, a file matched by several globs is only read through the
+    /// first of them so overlapping globs do not read it twice
+    pub fn earlier_globs(&self, glob: &PrefixGlob) -> Result<GlobSet, Error> {
+        build_glob_set(
+            self.globs
+                .iter()
+                .filter(|other| other.pattern < glob.pattern)
+                .map(|other| other.pattern.as_str()),
+        )
+    }
+
+    /// whether a file is read through one of the literal paths
+    pub fn covers(&self, location: &Path) -> bool {
+        self.paths.iter().any(|path| location.prefix_matches(path))
+    }
+}
+
+fn normalize_pattern(pattern: &str) -> String {
+    pattern.trim_matches('/').to_string()
+}
+
+pub fn build_glob(pattern: &str) -> Result<Glob, Error> {
+    // `*` should not cross directories, `**` is used for that
+    let glob = GlobBuilder::new(pattern).literal_separator(true).build()?;
+    Ok(glob)
+}
+
+fn build_glob_set<'a>(patterns: impl IntoIterator<Item = &'a str>) -> Result<GlobSet, Error> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        builder.add(build_glob(pattern)?);
+    }
+    Ok(builder.build()?)
+}
+
+/// dedups literal paths and drops globs whose base is already covered by a literal path,
+/// exclusions are kept as they apply to every path and glob
+pub fn dedup_patterns(patterns: &Vec<PrefixPattern>) -> PrefixPatterns {
+    let mut paths = Vec::new();
+    let mut globs = HashSet::new();
+    let mut exclusions = HashSet::new();
+
+    for pattern in patterns.iter() {
+        match pattern {
+            PrefixPattern::Path(path) => paths.push(path.clone()),
+            PrefixPattern::Glob(glob) => {
+                globs.insert(glob.clone());
+            }
+            PrefixPattern::Exclude(exclusion) => {
+                exclusions.insert(exclusion.clone());
+            }
+        }
+    }
+
+    let paths = dedup_paths(&paths);
+    globs.retain(|glob: &PrefixGlob| !paths.iter().any(|path| glob.base.prefix_matches(path)));
+
+    PrefixPatterns {
+        paths,
+        globs,
+        exclusions,
+    }
+}
+
 pub fn dedup_paths(paths: &Vec<Path>) -> HashSet<Path> {
     let mut deduped_paths = HashSet::<Path>::new();
     for path in paths.iter() {
@@ -181,3 +348,99 @@ mod test_dedup_paths {
         );
     }
 }
+
+#[cfg(test)]
+mod test_dedup_patterns {
+    use super::*;
+
+    fn parse(patterns: &[&str]) -> Vec<PrefixPattern> {
+        patterns
+            .iter()
+            .map(|pattern| PrefixPattern::parse(pattern).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn parses_glob_base() {
+        let pattern = PrefixPattern::parse("/events/2024-*/**/*.parquet").unwrap();
+
+        assert_eq!(
+            pattern,
+            PrefixPattern::Glob(PrefixGlob {
+                base: Path::parse("events").unwrap(),
+                pattern: String::from("events/2024-*/**/*.parquet"),
+            })
+        );
+    }
+
+    #[test]
+    fn parses_exclusions() {
+        let pattern = PrefixPattern::parse("!**/_tmp/**").unwrap();
+
+        assert_eq!(pattern, PrefixPattern::Exclude(String::from("**/_tmp/**")));
+    }
+
+    #[test]
+    fn glob_covered_by_path_is_removed() {
+        let patterns = parse(&["events", "events/2024-*/**/*.parquet", "logs/*.parquet"]);
+
+        let deduped = dedup_patterns(&patterns);
+        assert_eq!(deduped.paths.len(), 1);
+        assert_eq!(deduped.globs.len(), 1);
+        assert_eq!(
+            deduped.globs.iter().next().unwrap().pattern,
+            String::from("logs/*.parquet")
+        );
+        assert_eq!(deduped.requires_expansion(), true);
+    }
+
+    #[test]
+    fn exclusions_are_matched() {
+        let patterns = parse(&["events", "!**/_tmp/**"]);
+
+        let deduped = dedup_patterns(&patterns);
+        let exclusions = deduped.exclusion_matcher().unwrap();
+        assert_eq!(exclusions.is_match("events/_tmp/file1.parquet"), true);
+        assert_eq!(exclusions.is_match("events/2024-01/file1.parquet"), false);
+    }
+
+    #[test]
+    fn overlapping_globs_claim_files_once() {
+        let patterns = parse(&["a/*.parquet", "a/x*.parquet", "b"]);
+
+        let deduped = dedup_patterns(&patterns);
+        let glob = |pattern: &str| {
+            deduped
+                .globs
+                .iter()
+                .find(|glob| glob.pattern == pattern)
+                .unwrap()
+                .clone()
+        };
+
+        // `a/x1.parquet` matches both globs and is only read through the first
+        let earlier = deduped.earlier_globs(&glob("a/*.parquet")).unwrap();
+        assert_eq!(earlier.is_match("a/x1.parquet"), false);
+        let earlier = deduped.earlier_globs(&glob("a/x*.parquet")).unwrap();
+        assert_eq!(earlier.is_match("a/x1.parquet"), true);
+
+        assert_eq!(
+            deduped.covers(&Path::parse("b/file1.parquet").unwrap()),
+            true
+        );
+        assert_eq!(deduped.covers(&Path::parse("a/x1.parquet").unwrap()), false);
+    }
+
+    #[test]
+    fn glob_overlapping_path_skips_its_files() {
+        let patterns = parse(&["a/x", "a/*/*.parquet"]);
+
+        let deduped = dedup_patterns(&patterns);
+        assert_eq!(deduped.globs.len(), 1);
+        assert_eq!(deduped.covers(&Path::parse("a/x/1.parquet").unwrap()), true);
+        assert_eq!(
+            deduped.covers(&Path::parse("a/y/1.parquet").unwrap()),
+            false
+        );
+    }
+}
@@ -0,0 +1,93 @@
+use std::sync::Arc;
+
+use datafusion::arrow::{datatypes::Schema, ipc::writer::StreamWriter, record_batch::RecordBatch};
+
+use crate::errors::Error;
+
+/// the ipc stream is sent as a base64 string instead of a json array with a number per byte
+mod base64_data {
+    use base64::{engine::general_purpose::STANDARD, Engine};
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(data: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&STANDARD.encode(data))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        let data = String::deserialize(deserializer)?;
+        STANDARD.decode(data).map_err(D::Error::custom)
+    }
+}
+
+/// query results encoded as an arrow ipc stream, types are preserved exactly unlike json rows
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct IpcResult {
+    pub schema: Arc<Schema>,
+    pub num_rows: usize,
+    #[serde(with = "base64_data")]
+    pub data: Vec<u8>,
+}
+
+impl IpcResult {
+    pub fn new(schema: Arc<Schema>, batches: &[RecordBatch]) -> Result<Self, Error> {
+        let mut data = Vec::new();
+        {
+            let mut writer = StreamWriter::try_new(&mut data, &schema)?;
+            for batch in batches {
+                writer.write(batch)?;
+            }
+            writer.finish()?;
+        }
+
+        Ok(Self {
+            schema,
+            num_rows: batches.iter().map(|batch| batch.num_rows()).sum(),
+            data,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test_results {
+    use std::io::Cursor;
+
+    use datafusion::arrow::{
+        array::{Decimal128Array, Int64Array},
+        datatypes::{DataType, Field},
+        ipc::reader::StreamReader,
+    };
+
+    use super::*;
+
+    #[test]
+    fn encodes_batches_as_ipc_stream() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new("amount", DataType::Decimal128(10, 2), true),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int64Array::from(vec![1, 2])),
+                Arc::new(
+                    Decimal128Array::from(vec![Some(1250), None])
+                        .with_precision_and_scale(10, 2)
+                        .unwrap(),
+                ),
+            ],
+        )
+        .unwrap();
+
+        let result = IpcResult::new(schema.clone(), &[batch.clone()]).unwrap();
+        assert_eq!(result.num_rows, 2);
+
+        let json = serde_json::to_value(&result).unwrap();
+        assert!(json["data"].is_string());
+        let result: IpcResult = serde_json::from_value(json).unwrap();
+
+        let reader = StreamReader::try_new(Cursor::new(result.data), None).unwrap();
+        assert_eq!(reader.schema(), schema);
+        let batches: Vec<RecordBatch> = reader.map(|batch| batch.unwrap()).collect();
+        assert_eq!(batches, vec![batch]);
+    }
+}
@@ -0,0 +1,364 @@
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap},
+    hash::{Hash, Hasher},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use datafusion::arrow::datatypes::{DataType, Field, Schema};
+use futures::lock::Mutex;
+use tracing::warn;
+
+#[derive(Debug, Clone)]
+pub struct CachedSchema {
+    pub schema: Arc<Schema>,
+    /// the fingerprint of the buffer prefixes and files the schema was inferred from
+    pub fingerprint: String,
+    /// false until the schema has been saved to the events file
+    pub persisted: bool,
+    /// when the files were last listed to check the schema, `None` for a schema loaded from the
+    /// events file
+    pub checked_at: Option<Instant>,
+}
+
+impl CachedSchema {
+    /// whether the schema was inferred for the same sources and its files were checked within
+    /// `ttl`, a current schema is used without listing the files again
+    pub fn is_current(&self, sources: &str, ttl: Duration) -> bool {
+        let is_same_sources = match self.fingerprint.rsplit_once('#') {
+            Some((cached_sources, _)) => cached_sources == sources,
+            None => false,
+        };
+        let is_recent = self
+            .checked_at
+            .map_or(false, |checked_at| checked_at.elapsed() < ttl);
+
+        is_same_sources && is_recent
+    }
+}
+
+pub type SchemaCache = Arc<Mutex<Option<CachedSchema>>>;
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq)]
+pub struct ColumnOverride {
+    pub name: String,
+    pub data_type: DataType,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default)]
+pub struct SchemaOverride {
+    /// used instead of inferring the schema
+    pub schema: Option<Arc<Schema>>,
+    /// replaces the inferred types of these columns, values are cast when the files are read
+    pub columns: Vec<ColumnOverride>,
+}
+
+impl SchemaOverride {
+    pub fn apply(&self, inferred: Arc<Schema>) -> Arc<Schema> {
+        if self.columns.is_empty() {
+            return inferred;
+        }
+
+        for column in self.columns.iter() {
+            if inferred.field_with_name(&column.name).is_err() {
+                warn!(column = %column.name, "column override does not match any field");
+            }
+        }
+
+        let fields: Vec<Field> = inferred
+            .fields()
+            .iter()
+            .map(|field| {
+                let column = self
+                    .columns
+                    .iter()
+                    .find(|column| &column.name == field.name());
+                match column {
+                    Some(column) => field
+                        .as_ref()
+                        .clone()
+                        .with_data_type(column.data_type.clone()),
+                    None => field.as_ref().clone(),
+                }
+            })
+            .collect();
+
+        Arc::new(Schema::new_with_metadata(
+            fields,
+            inferred.metadata().clone(),
+        ))
+    }
+}
+
+/// a hash of the names, types and nullability of the fields, schema metadata is ignored
+pub fn fingerprint(schema: &Schema) -> String {
+    let mut hasher = DefaultHasher::new();
+    for field in schema.fields().iter() {
+        field.name().hash(&mut hasher);
+        field.data_type().hash(&mut hasher);
+        field.is_nullable().hash(&mut hasher);
+    }
+
+    format!("{:016x}", hasher.finish())
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct FileLocation {
+    pub store: usize,
+    pub location: String,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct SchemaGroup {
+    pub fingerprint: String,
+    pub schema: Arc<Schema>,
+    pub files: Vec<FileLocation>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq)]
+pub enum FieldDiff {
+    /// the field is in the reference schema but not in the compared schema
+    Missing { name: String },
+    /// the field is in the compared schema but not in the reference schema
+    Added { name: String, data_type: DataType },
+    TypeChanged {
+        name: String,
+        reference: DataType,
+        compared: DataType,
+    },
+    NullabilityChanged {
+        name: String,
+        reference: bool,
+        compared: bool,
+    },
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct SchemaDiff {
+    pub reference: String,
+    pub compared: String,
+    pub fields: Vec<FieldDiff>,
+}
+
+impl SchemaDiff {
+    pub fn new(reference: &SchemaGroup, compared: &SchemaGroup) -> Self {
+        let mut fields = Vec::new();
+
+        for field in reference.schema.fields().iter() {
+            match compared.schema.field_with_name(field.name()) {
+                Ok(other) => {
+                    if field.data_type() != other.data_type() {
+                        fields.push(FieldDiff::TypeChanged {
+                            name: field.name().clone(),
+                            reference: field.data_type().clone(),
+                            compared: other.data_type().clone(),
+                        });
+                    }
+                    if field.is_nullable() != other.is_nullable() {
+                        fields.push(FieldDiff::NullabilityChanged {
+                            name: field.name().clone(),
+                            reference: field.is_nullable(),
+                            compared: other.is_nullable(),
+                        });
+                    }
+                }
+                Err(_) => fields.push(FieldDiff::Missing {
+                    name: field.name().clone(),
+                }),
+            }
+        }
+
+        for field in compared.schema.fields().iter() {
+            if reference.schema.field_with_name(field.name()).is_err() {
+                fields.push(FieldDiff::Added {
+                    name: field.name().clone(),
+                    data_type: field.data_type().clone(),
+                });
+            }
+        }
+
+        Self {
+            reference: reference.fingerprint.clone(),
+            compared: compared.fingerprint.clone(),
+            fields,
+        }
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct SchemaFailure {
+    pub file: FileLocation,
+    pub error: String,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct SchemaComparison {
+    pub num_files: usize,
+    pub num_sampled: usize,
+    /// sorted by the number of files, the first group is used as the reference for the diffs
+    pub groups: Vec<SchemaGroup>,
+    pub diffs: Vec<SchemaDiff>,
+    pub failures: Vec<SchemaFailure>,
+}
+
+impl SchemaComparison {
+    pub fn new(
+        num_files: usize,
+        schemas: Vec<(FileLocation, Arc<Schema>)>,
+        failures: Vec<SchemaFailure>,
+    ) -> Self {
+        let num_sampled = schemas.len() + failures.len();
+        let mut groups: HashMap<String, SchemaGroup> = HashMap::new();
+
+        for (file, schema) in schemas {
+            let fingerprint = fingerprint(&schema);
+            groups
+                .entry(fingerprint.clone())
+                .or_insert_with(|| SchemaGroup {
+                    fingerprint,
+                    schema,
+                    files: Vec::new(),
+                })
+                .files
+                .push(file);
+        }
+
+        let mut groups: Vec<SchemaGroup> = groups.into_values().collect();
+        groups.sort_by(|a, b| {
+            b.files
+                .len()
+                .cmp(&a.files.len())
+                .then(a.fingerprint.cmp(&b.fingerprint))
+        });
+
+        let diffs = match groups.split_first() {
+            Some((reference, others)) => others
+                .iter()
+                .map(|group| SchemaDiff::new(reference, group))
+                .collect(),
+            None => Vec::new(),
+        };
+
+        Self {
+            num_files,
+            num_sampled,
+            groups,
+            diffs,
+            failures,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_schema_comparison {
+    use super::*;
+
+    fn file(location: &str) -> FileLocation {
+        FileLocation {
+            store: 1,
+            location: String::from(location),
+        }
+    }
+
+    #[test]
+    fn groups_files_by_schema() {
+        let reference = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new("name", DataType::Utf8, true),
+        ]));
+        let odd = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Utf8, false),
+            Field::new("email", DataType::Utf8, true),
+        ]));
+
+        let comparison = SchemaComparison::new(
+            3,
+            vec![
+                (file("a.parquet"), reference.clone()),
+                (file("b.parquet"), odd.clone()),
+                (file("c.parquet"), reference.clone()),
+            ],
+            Vec::new(),
+        );
+
+        assert_eq!(comparison.groups.len(), 2);
+        assert_eq!(comparison.groups[0].files.len(), 2);
+        assert_eq!(comparison.groups[1].files, vec![file("b.parquet")]);
+        assert_eq!(
+            comparison.diffs[0].fields,
+            vec![
+                FieldDiff::TypeChanged {
+                    name: String::from("id"),
+                    reference: DataType::Int64,
+                    compared: DataType::Utf8,
+                },
+                FieldDiff::Missing {
+                    name: String::from("name"),
+                },
+                FieldDiff::Added {
+                    name: String::from("email"),
+                    data_type: DataType::Utf8,
+                },
+            ]
+        );
+    }
+}
+
+#[cfg(test)]
+mod test_schema_override {
+    use super::*;
+
+    #[test]
+    fn overrides_column_types() {
+        let inferred = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new("created_at", DataType::Utf8, true),
+        ]));
+        let schema_override = SchemaOverride {
+            schema: None,
+            columns: vec![ColumnOverride {
+                name: String::from("id"),
+                data_type: DataType::Utf8,
+            }],
+        };
+
+        let schema = schema_override.apply(inferred);
+        assert_eq!(schema.field(0).data_type(), &DataType::Utf8);
+        assert_eq!(schema.field(0).is_nullable(), false);
+        assert_eq!(schema.field(1).data_type(), &DataType::Utf8);
+    }
+}
+
+#[cfg(test)]
+mod test_cached_schema {
+    use super::*;
+
+    fn cached(checked_at: Option<Instant>) -> CachedSchema {
+        CachedSchema {
+            schema: Arc::new(Schema::empty()),
+            fingerprint: String::from("0:events#2:ab12"),
+            persisted: true,
+            checked_at,
+        }
+    }
+
+    #[test]
+    fn is_current_within_ttl() {
+        let ttl = Duration::from_secs(60);
+
+        assert_eq!(
+            cached(Some(Instant::now())).is_current("0:events", ttl),
+            true
+        );
+        assert_eq!(
+            cached(Some(Instant::now())).is_current("0:logs", ttl),
+            false
+        );
+        assert_eq!(
+            cached(Some(Instant::now())).is_current("0:events", Duration::ZERO),
+            false
+        );
+        // a schema loaded from the events file is checked against the files once
+        assert_eq!(cached(None).is_current("0:events", ttl), false);
+    }
+}
@@ -0,0 +1,294 @@
+use std::{any::Any, sync::Arc};
+
+use async_trait::async_trait;
+use datafusion::{
+    arrow::datatypes::{DataType, Field, Schema, SchemaRef, TimeUnit},
+    common::{ScalarValue, Statistics, ToDFSchema},
+    datasource::{
+        listing::{ListingOptions, ListingTableUrl, PartitionedFile},
+        physical_plan::FileScanConfig,
+        TableProvider, TableType,
+    },
+    error::{DataFusionError, Result},
+    execution::context::SessionState,
+    logical_expr::{utils::conjunction, Expr, TableProviderFilterPushDown},
+    physical_expr::{create_physical_expr, PhysicalExpr},
+    physical_plan::{empty::EmptyExec, union::UnionExec, ExecutionPlan},
+};
+use object_store::ObjectMeta;
+
+use crate::state::store::ObjectStore;
+
+use super::FileSystemBuffer;
+
+pub const SOURCE_FILE: &str = "source_file";
+pub const SOURCE_STORE: &str = "source_store";
+pub const SOURCE_SIZE: &str = "source_size";
+pub const SOURCE_LAST_MODIFIED: &str = "source_last_modified";
+
+/// the columns appended to the file columns, in order
+pub fn source_fields() -> Vec<Field> {
+    vec![
+        Field::new(SOURCE_FILE, DataType::Utf8, false),
+        Field::new(SOURCE_STORE, DataType::Utf8, false),
+        Field::new(SOURCE_SIZE, DataType::UInt64, false),
+        Field::new(
+            SOURCE_LAST_MODIFIED,
+            DataType::Timestamp(TimeUnit::Millisecond, Some("UTC".into())),
+            false,
+        ),
+    ]
+}
+
+/// a buffer's files with columns for the file each row was read from. the source columns are
+/// partition columns whose values are set per file, so the files of each store are scanned by a
+/// single parquet scan
+#[derive(Debug)]
+pub struct SourcesTable {
+    file_schema: SchemaRef,
+    schema: SchemaRef,
+    options: ListingOptions,
+    file_systems: Vec<FileSystemBuffer>,
+}
+
+impl SourcesTable {
+    /// `None` if the files already have a column with the name of a source column
+    pub fn new(
+        file_schema: SchemaRef,
+        options: ListingOptions,
+        file_systems: Vec<FileSystemBuffer>,
+    ) -> Option<Self> {
+        let sources = source_fields();
+        let is_conflicting = sources
+            .iter()
+            .any(|field| file_schema.field_with_name(field.name()).is_ok());
+        if is_conflicting {
+            return None;
+        }
+
+        let mut fields: Vec<Field> = file_schema
+            .fields()
+            .iter()
+            .map(|field| field.as_ref().clone())
+            .collect();
+        fields.extend(sources);
+
+        Some(Self {
+            schema: Arc::new(Schema::new(fields)),
+            file_schema,
+            options,
+            file_systems,
+        })
+    }
+
+    fn source_values(store: &ObjectStore, meta: &ObjectMeta) -> Vec<ScalarValue> {
+        vec![
+            ScalarValue::Utf8(Some(meta.location.to_string())),
+            ScalarValue::Utf8(Some(store.metadata.name.clone())),
+            ScalarValue::UInt64(Some(meta.size as u64)),
+            ScalarValue::TimestampMillisecond(
+                Some(meta.last_modified.timestamp_millis()),
+                Some("UTC".into()),
+            ),
+        ]
+    }
+
+    /// only filters on the file columns can prune the files, all filters are applied again
+    fn file_filter(
+        &self,
+        state: &SessionState,
+        filters: &[Expr],
+    ) -> Result<Option<Arc<dyn PhysicalExpr>>> {
+        let mut file_filters = Vec::new();
+        for filter in filters {
+            let is_file_filter = filter
+                .to_columns()?
+                .iter()
+                .all(|column| self.file_schema.field_with_name(&column.name).is_ok());
+            if is_file_filter {
+                file_filters.push(filter.clone());
+            }
+        }
+
+        match conjunction(file_filters) {
+            Some(filter) => {
+                let df_schema = self.schema.as_ref().clone().to_dfschema()?;
+                let filter = create_physical_expr(&filter, &df_schema, state.execution_props())?;
+                Ok(Some(filter))
+            }
+            None => Ok(None),
+        }
+    }
+}
+
+#[async_trait]
+impl TableProvider for SourcesTable {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        self.schema.clone()
+    }
+
+    fn table_type(&self) -> TableType {
+        TableType::Base
+    }
+
+    fn supports_filters_pushdown(
+        &self,
+        filters: &[&Expr],
+    ) -> Result<Vec<TableProviderFilterPushDown>> {
+        Ok(vec![TableProviderFilterPushDown::Inexact; filters.len()])
+    }
+
+    async fn scan(
+        &self,
+        state: &SessionState,
+        projection: Option<&Vec<usize>>,
+        filters: &[Expr],
+        limit: Option<usize>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        let filter = self.file_filter(state, filters)?;
+        let num_groups = state.config().target_partitions().max(1);
+
+        let mut plans = Vec::new();
+        for file_system in self.file_systems.iter() {
+            let store = &file_system.store;
+            let files: Vec<PartitionedFile> = file_system
+                .list_files()
+                .await
+                .map_err(|e| DataFusionError::External(Box::new(e)))?
+                .into_iter()
+                .map(|meta| {
+                    let mut file = PartitionedFile::from(meta);
+                    file.partition_values = Self::source_values(store, &file.object_meta);
+                    file
+                })
+                .collect();
+            if files.is_empty() {
+                continue;
+            }
+
+            let files_per_group = (files.len() + num_groups - 1) / num_groups;
+            let config = FileScanConfig {
+                object_store_url: ListingTableUrl::parse(store.url())?.object_store(),
+                file_schema: self.file_schema.clone(),
+                file_groups: files
+                    .chunks(files_per_group)
+                    .map(|group| group.to_vec())
+                    .collect(),
+                statistics: Statistics::new_unknown(&self.file_schema),
+                projection: projection.cloned(),
+                limit,
+                table_partition_cols: source_fields(),
+                output_ordering: Vec::new(),
+                infinite_source: false,
+            };
+            let plan = self
+                .options
+                .format
+                .create_physical_plan(state, config, filter.as_ref())
+                .await?;
+            plans.push(plan);
+        }
+
+        match plans.len() {
+            0 => {
+                let schema = match projection {
+                    Some(projection) => Arc::new(self.schema.project(projection)?),
+                    None => self.schema.clone(),
+                };
+                Ok(Arc::new(EmptyExec::new(false, schema)))
+            }
+            1 => Ok(plans.remove(0)),
+            _ => Ok(Arc::new(UnionExec::new(plans))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_sources_table {
+    use datafusion::{
+        arrow::{
+            array::{AsArray, Int64Array},
+            compute::concat_batches,
+            datatypes::{Int64Type, UInt64Type},
+            record_batch::RecordBatch,
+        },
+        datasource::file_format::parquet::ParquetFormat,
+        parquet::arrow::ArrowWriter,
+        prelude::SessionContext,
+    };
+
+    use crate::{
+        query::PrefixPattern,
+        state::store::{Connection, LocalConnection, Metadata, ObjectStoreKind},
+    };
+
+    use super::*;
+
+    fn write(path: &std::path::Path, schema: &SchemaRef, ids: Vec<i64>) {
+        let batch =
+            RecordBatch::try_new(schema.clone(), vec![Arc::new(Int64Array::from(ids))]).unwrap();
+        let file = std::fs::File::create(path).unwrap();
+        let mut writer = ArrowWriter::try_new(file, schema.clone(), None).unwrap();
+        writer.write(&batch).unwrap();
+        writer.close().unwrap();
+    }
+
+    #[tokio::test]
+    async fn adds_source_columns_per_file() {
+        let dir = std::env::temp_dir().join(format!("file-fusion-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let schema = Arc::new(Schema::new(vec![Field::new("id", DataType::Int64, false)]));
+        write(&dir.join("a.parquet"), &schema, vec![1, 2]);
+        write(&dir.join("b.parquet"), &schema, vec![3]);
+
+        let store = ObjectStore::new(
+            Metadata {
+                id: 0,
+                name: String::from("Local"),
+                prefix: String::new(),
+                kind: ObjectStoreKind::Local,
+            },
+            Connection::Local(LocalConnection {}),
+        )
+        .unwrap();
+        let prefix = dir.to_str().unwrap().trim_start_matches('/');
+        let file_system =
+            FileSystemBuffer::new(store, &vec![PrefixPattern::parse(prefix).unwrap()]);
+        let options =
+            ListingOptions::new(Arc::new(ParquetFormat::default())).with_file_extension(".parquet");
+        let table = SourcesTable::new(schema, options, vec![file_system]).unwrap();
+
+        let ctx = SessionContext::new();
+        ctx.register_table("sources", Arc::new(table)).unwrap();
+        let batches = ctx
+            .sql("SELECT id, source_file, source_size FROM sources WHERE id > 1 ORDER BY id")
+            .await
+            .unwrap()
+            .collect()
+            .await
+            .unwrap();
+        let batch = &concat_batches(&batches[0].schema(), &batches).unwrap();
+
+        let ids: Vec<i64> = batch
+            .column(0)
+            .as_primitive::<Int64Type>()
+            .values()
+            .to_vec();
+        assert_eq!(ids, vec![2, 3]);
+        let files: Vec<&str> = batch
+            .column(1)
+            .as_string::<i32>()
+            .iter()
+            .flatten()
+            .collect();
+        assert!(files[0].ends_with("a.parquet"));
+        assert!(files[1].ends_with("b.parquet"));
+        assert!(batch.column(2).as_primitive::<UInt64Type>().value(0) > 0);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
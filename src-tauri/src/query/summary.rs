@@ -0,0 +1,338 @@
+use datafusion::{
+    arrow::{
+        array::{Array, ArrayRef, AsArray},
+        datatypes::{DataType, Float64Type, Int64Type, UInt64Type},
+        record_batch::RecordBatch,
+        util::display::array_value_to_string,
+    },
+    dataframe::DataFrame,
+    logical_expr::{
+        cast,
+        expr_fn::{approx_distinct, approx_percentile_cont, avg, count, max, min},
+        ident, lit, Expr,
+    },
+};
+
+use crate::errors::Error;
+
+const PERCENTILES: [f64; 3] = [0.25, 0.5, 0.75];
+
+/// reserved so it does not collide with a column of the query result
+const TOP_VALUE_COUNT: &str = "__count";
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct Percentile {
+    pub percentile: f64,
+    /// approximated, `None` when the column only has nulls
+    pub value: Option<f64>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct ValueCount {
+    /// `None` for nulls
+    pub value: Option<String>,
+    pub count: i64,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct ColumnSummary {
+    pub name: String,
+    pub data_type: DataType,
+    /// the number of non null values
+    pub count: i64,
+    pub null_count: i64,
+    /// nested columns are not summarized beyond their counts
+    pub distinct_estimate: Option<u64>,
+    pub min: Option<String>,
+    pub max: Option<String>,
+    /// numeric columns only
+    pub mean: Option<f64>,
+    pub percentiles: Vec<Percentile>,
+    pub top_values: Vec<ValueCount>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct ResultSummary {
+    pub num_rows: i64,
+    pub columns: Vec<ColumnSummary>,
+}
+
+fn is_nested(data_type: &DataType) -> bool {
+    matches!(
+        data_type,
+        DataType::List(_)
+            | DataType::LargeList(_)
+            | DataType::FixedSizeList(_, _)
+            | DataType::Struct(_)
+            | DataType::Map(_, _)
+            | DataType::Union(_, _)
+    )
+}
+
+/// the types `approx_distinct` accepts, other columns are estimated from their string values
+fn has_distinct_estimate(data_type: &DataType) -> bool {
+    data_type.is_integer()
+        || matches!(
+            data_type,
+            DataType::Utf8 | DataType::LargeUtf8 | DataType::Binary | DataType::LargeBinary
+        )
+}
+
+fn alias(column: usize, name: &str) -> String {
+    format!("c{}_{}", column, name)
+}
+
+fn column<'a>(batch: &'a RecordBatch, name: &str) -> Option<&'a ArrayRef> {
+    batch.column_by_name(name).filter(|array| array.is_valid(0))
+}
+
+fn int64(batch: &RecordBatch, name: &str) -> i64 {
+    column(batch, name)
+        .and_then(|array| array.as_primitive_opt::<Int64Type>())
+        .map_or(0, |array| array.value(0))
+}
+
+fn uint64(batch: &RecordBatch, name: &str) -> Option<u64> {
+    column(batch, name)
+        .and_then(|array| array.as_primitive_opt::<UInt64Type>())
+        .map(|array| array.value(0))
+}
+
+fn float64(batch: &RecordBatch, name: &str) -> Option<f64> {
+    column(batch, name)
+        .and_then(|array| array.as_primitive_opt::<Float64Type>())
+        .map(|array| array.value(0))
+}
+
+fn string(batch: &RecordBatch, name: &str) -> Result<Option<String>, Error> {
+    match column(batch, name) {
+        Some(array) => Ok(Some(array_value_to_string(array, 0)?)),
+        None => Ok(None),
+    }
+}
+
+impl ResultSummary {
+    /// summarizes every row of the query result with one aggregation over all columns, the most
+    /// frequent values take one grouped aggregation per column and are only computed when `top_k`
+    /// is set
+    pub async fn compute(df: DataFrame, top_k: Option<usize>) -> Result<Self, Error> {
+        let fields: Vec<(Expr, String, DataType)> = df
+            .schema()
+            .fields()
+            .iter()
+            .map(|field| {
+                (
+                    Expr::Column(field.qualified_column()),
+                    field.name().clone(),
+                    field.data_type().clone(),
+                )
+            })
+            .collect();
+
+        let mut aggregates = vec![count(lit(1)).alias("num_rows")];
+        for (i, (expr, _, data_type)) in fields.iter().enumerate() {
+            let expr = expr.clone();
+            aggregates.push(count(expr.clone()).alias(alias(i, "count")));
+
+            if is_nested(data_type) {
+                continue;
+            }
+            let distinct = if has_distinct_estimate(data_type) {
+                approx_distinct(expr.clone())
+            } else {
+                approx_distinct(cast(expr.clone(), DataType::Utf8))
+            };
+            aggregates.push(distinct.alias(alias(i, "distinct")));
+            aggregates.push(min(expr.clone()).alias(alias(i, "min")));
+            aggregates.push(max(expr.clone()).alias(alias(i, "max")));
+
+            if data_type.is_numeric() {
+                let value = cast(expr, DataType::Float64);
+                aggregates.push(avg(value.clone()).alias(alias(i, "mean")));
+                for (p, percentile) in PERCENTILES.iter().enumerate() {
+                    aggregates.push(
+                        approx_percentile_cont(value.clone(), lit(*percentile))
+                            .alias(alias(i, &format!("p{}", p))),
+                    );
+                }
+            }
+        }
+
+        let batches = df.clone().aggregate(vec![], aggregates)?.collect().await?;
+        let batch = batches
+            .first()
+            .ok_or(Error::NotFound(String::from("summary row")))?;
+
+        let num_rows = int64(batch, "num_rows");
+        let mut columns = Vec::with_capacity(fields.len());
+        for (i, (expr, name, data_type)) in fields.into_iter().enumerate() {
+            let count = int64(batch, &alias(i, "count"));
+
+            let percentiles = if data_type.is_numeric() {
+                PERCENTILES
+                    .iter()
+                    .enumerate()
+                    .map(|(p, percentile)| Percentile {
+                        percentile: *percentile,
+                        value: float64(batch, &alias(i, &format!("p{}", p))),
+                    })
+                    .collect()
+            } else {
+                Vec::new()
+            };
+
+            let top_values = match top_k {
+                Some(top_k) if !is_nested(&data_type) => top_values(&df, expr, top_k).await?,
+                _ => Vec::new(),
+            };
+
+            columns.push(ColumnSummary {
+                count,
+                null_count: num_rows - count,
+                distinct_estimate: uint64(batch, &alias(i, "distinct")),
+                min: string(batch, &alias(i, "min"))?,
+                max: string(batch, &alias(i, "max"))?,
+                mean: float64(batch, &alias(i, "mean")),
+                percentiles,
+                top_values,
+                name,
+                data_type,
+            });
+        }
+
+        Ok(Self { num_rows, columns })
+    }
+}
+
+async fn top_values(df: &DataFrame, column: Expr, top_k: usize) -> Result<Vec<ValueCount>, Error> {
+    let batches = df
+        .clone()
+        .aggregate(vec![column], vec![count(lit(1)).alias(TOP_VALUE_COUNT)])?
+        .sort(vec![ident(TOP_VALUE_COUNT).sort(false, true)])?
+        .limit(0, Some(top_k))?
+        .collect()
+        .await?;
+
+    let mut values = Vec::new();
+    for batch in batches.iter() {
+        let counts = batch.column(1).as_primitive::<Int64Type>();
+        for row in 0..batch.num_rows() {
+            let value = if batch.column(0).is_null(row) {
+                None
+            } else {
+                Some(array_value_to_string(batch.column(0), row)?)
+            };
+            values.push(ValueCount {
+                value,
+                count: counts.value(row),
+            });
+        }
+    }
+
+    Ok(values)
+}
+
+#[cfg(test)]
+mod test_summary {
+    use datafusion::{
+        arrow::{
+            array::{BooleanArray, Date32Array, Float64Array, Int64Array, StringArray},
+            datatypes::{Field, Schema},
+        },
+        datasource::MemTable,
+        prelude::SessionContext,
+    };
+    use std::sync::Arc;
+
+    use super::*;
+
+    async fn frame() -> DataFrame {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new("name", DataType::Utf8, true),
+            Field::new("price", DataType::Float64, true),
+            Field::new("active", DataType::Boolean, true),
+            Field::new("day", DataType::Date32, true),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int64Array::from(vec![1, 2, 3, 4])),
+                Arc::new(StringArray::from(vec![
+                    Some("a"),
+                    Some("b"),
+                    Some("a"),
+                    None,
+                ])),
+                Arc::new(Float64Array::from(vec![
+                    Some(1.5),
+                    Some(2.5),
+                    None,
+                    Some(2.5),
+                ])),
+                Arc::new(BooleanArray::from(vec![
+                    Some(true),
+                    Some(false),
+                    Some(true),
+                    None,
+                ])),
+                Arc::new(Date32Array::from(vec![
+                    Some(19000),
+                    Some(19001),
+                    None,
+                    None,
+                ])),
+            ],
+        )
+        .unwrap();
+
+        let ctx = SessionContext::new();
+        let table = MemTable::try_new(schema, vec![vec![batch]]).unwrap();
+        ctx.read_table(Arc::new(table)).unwrap()
+    }
+
+    fn column<'a>(summary: &'a ResultSummary, name: &str) -> &'a ColumnSummary {
+        summary
+            .columns
+            .iter()
+            .find(|column| column.name == name)
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn summarizes_mixed_columns() {
+        let summary = ResultSummary::compute(frame().await, None).await.unwrap();
+
+        assert_eq!(summary.num_rows, 4);
+        let price = column(&summary, "price");
+        assert_eq!(price.null_count, 1);
+        assert_eq!(price.distinct_estimate, Some(2));
+        assert!((price.mean.unwrap() - 6.5 / 3.0).abs() < 1e-9);
+        assert_eq!(price.percentiles.len(), PERCENTILES.len());
+
+        let active = column(&summary, "active");
+        assert_eq!(active.distinct_estimate, Some(2));
+        assert!(active.percentiles.is_empty());
+
+        let day = column(&summary, "day");
+        assert_eq!(day.count, 2);
+        assert_eq!(day.distinct_estimate, Some(2));
+        assert_eq!(day.min, Some(String::from("2022-01-08")));
+
+        // the most frequent values are only computed when asked for
+        assert!(column(&summary, "name").top_values.is_empty());
+    }
+
+    #[tokio::test]
+    async fn counts_top_values() {
+        let summary = ResultSummary::compute(frame().await, Some(1))
+            .await
+            .unwrap();
+
+        let name = column(&summary, "name");
+        assert_eq!(name.distinct_estimate, Some(2));
+        assert_eq!(name.top_values.len(), 1);
+        assert_eq!(name.top_values[0].value, Some(String::from("a")));
+        assert_eq!(name.top_values[0].count, 2);
+    }
+}
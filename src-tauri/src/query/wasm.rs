@@ -0,0 +1,285 @@
+use std::{path::Path, sync::Arc};
+
+use datafusion::{
+    arrow::{
+        array::{Array, ArrayRef, AsArray, Float32Array, Float64Array, Int32Array, Int64Array},
+        datatypes::{DataType, Float32Type, Float64Type, Int32Type, Int64Type},
+    },
+    common::{DataFusionError, ScalarValue},
+    logical_expr::{create_udf, ColumnarValue, ScalarUDF, Volatility},
+};
+use tracing::debug;
+use wasmtime::{Config, Engine, Instance, Module, Store, Val, ValType};
+
+use crate::errors::Error;
+
+use super::functions::{arguments, is_scalar, FunctionDefinition};
+
+/// the fuel a module gets to instantiate and for each call, roughly the number of instructions
+/// it may run. a call that runs out traps so a module that loops forever fails the query
+const FUEL_PER_CALL: u64 = 10_000_000;
+
+/// only numbers can be passed to a module without agreeing on how memory is shared
+fn is_wasm_type(val_type: &ValType, data_type: &DataType) -> bool {
+    matches!(
+        (val_type, data_type),
+        (ValType::I32, DataType::Int32)
+            | (ValType::I64, DataType::Int64)
+            | (ValType::F32, DataType::Float32)
+            | (ValType::F64, DataType::Float64)
+    )
+}
+
+/// `None` for nulls
+fn value(array: &ArrayRef, row: usize) -> Option<Val> {
+    if array.is_null(row) {
+        return None;
+    }
+
+    match array.data_type() {
+        DataType::Int32 => Some(Val::I32(array.as_primitive::<Int32Type>().value(row))),
+        DataType::Int64 => Some(Val::I64(array.as_primitive::<Int64Type>().value(row))),
+        DataType::Float32 => Some(Val::F32(
+            array.as_primitive::<Float32Type>().value(row).to_bits(),
+        )),
+        DataType::Float64 => Some(Val::F64(
+            array.as_primitive::<Float64Type>().value(row).to_bits(),
+        )),
+        _ => None,
+    }
+}
+
+fn array(data_type: &DataType, values: &[Option<Val>]) -> Result<ArrayRef, DataFusionError> {
+    let values = values.iter().map(Option::as_ref);
+    let array: ArrayRef = match data_type {
+        DataType::Int32 => Arc::new(values.map(|v| v.and_then(Val::i32)).collect::<Int32Array>()),
+        DataType::Int64 => Arc::new(values.map(|v| v.and_then(Val::i64)).collect::<Int64Array>()),
+        DataType::Float32 => Arc::new(
+            values
+                .map(|v| v.and_then(Val::f32))
+                .collect::<Float32Array>(),
+        ),
+        DataType::Float64 => Arc::new(
+            values
+                .map(|v| v.and_then(Val::f64))
+                .collect::<Float64Array>(),
+        ),
+        _ => {
+            return Err(DataFusionError::Internal(format!(
+                "wasm functions cannot return {}",
+                data_type
+            )))
+        }
+    };
+
+    Ok(array)
+}
+
+/// loads the module named by the body from `modules_dir`. the module exports a function with the
+/// name of the definition that takes and returns the wasm number types of the column types, it
+/// is not called for rows with a null argument
+pub fn compile(definition: &FunctionDefinition, modules_dir: &Path) -> Result<ScalarUDF, Error> {
+    let is_file_name = Path::new(&definition.body)
+        .file_name()
+        .map_or(false, |name| name == definition.body.as_str());
+    if !is_file_name {
+        return Err(Error::InvalidFunction(format!(
+            "`{}` is not the file name of a module in {}",
+            definition.body,
+            modules_dir.display()
+        )));
+    }
+    let file = modules_dir.join(&definition.body);
+
+    let mut config = Config::new();
+    config.consume_fuel(true);
+    let engine = Engine::new(&config)
+        .map_err(|e| Error::InvalidFunction(format!("failed to create wasm engine: {}", e)))?;
+    let module = Module::from_file(&engine, &file)
+        .map_err(|e| Error::InvalidFunction(format!("failed to load {}: {}", file.display(), e)))?;
+
+    // the signature is checked once so calls only fail when the module traps
+    let mut store = Store::new(&engine, ());
+    store
+        .set_fuel(FUEL_PER_CALL)
+        .map_err(|e| Error::InvalidFunction(e.to_string()))?;
+    let instance = Instance::new(&mut store, &module, &[]).map_err(|e| {
+        Error::InvalidFunction(format!("failed to instantiate {}: {}", file.display(), e))
+    })?;
+    let func = instance
+        .get_func(&mut store, &definition.name)
+        .ok_or_else(|| {
+            Error::InvalidFunction(format!(
+                "{} does not export `{}`",
+                file.display(),
+                definition.name
+            ))
+        })?;
+    let ty = func.ty(&store);
+    let params: Vec<ValType> = ty.params().collect();
+    let results: Vec<ValType> = ty.results().collect();
+    let signature_matches = params.len() == definition.params.len()
+        && params
+            .iter()
+            .zip(definition.params.iter())
+            .all(|(val_type, param)| is_wasm_type(val_type, &param.data_type))
+        && results.len() == 1
+        && is_wasm_type(&results[0], &definition.return_type);
+    if !signature_matches {
+        return Err(Error::InvalidFunction(format!(
+            "`{}` in {} does not match the parameter and return types, only Int32, Int64, \
+             Float32 and Float64 are supported",
+            definition.name,
+            file.display()
+        )));
+    }
+    debug!(function = %definition.name, module = ?file, "loaded wasm function");
+
+    let name = definition.name.clone();
+    let return_type = definition.return_type.clone();
+    let fun = Arc::new(move |args: &[ColumnarValue]| {
+        let (columns, num_rows) = arguments(args)?;
+
+        // a store per batch, a trapped instance is not reused
+        let mut store = Store::new(&engine, ());
+        let execution_error = |e: wasmtime::Error| DataFusionError::Execution(e.to_string());
+        store.set_fuel(FUEL_PER_CALL).map_err(execution_error)?;
+        let instance = Instance::new(&mut store, &module, &[]).map_err(execution_error)?;
+        let func = instance
+            .get_func(&mut store, &name)
+            .ok_or_else(|| DataFusionError::Execution(format!("`{}` is not exported", name)))?;
+
+        let mut values = Vec::with_capacity(num_rows);
+        let mut params = Vec::with_capacity(columns.len());
+        let mut results = [Val::I32(0)];
+        for row in 0..num_rows {
+            params.clear();
+            params.extend(columns.iter().map_while(|column| value(column, row)));
+            if params.len() < columns.len() {
+                values.push(None);
+                continue;
+            }
+
+            store.set_fuel(FUEL_PER_CALL).map_err(execution_error)?;
+            func.call(&mut store, &params, &mut results)
+                .map_err(|e| DataFusionError::Execution(format!("`{}` failed: {:#}", name, e)))?;
+            values.push(Some(results[0].clone()));
+        }
+
+        let array = array(&return_type, &values)?;
+        if is_scalar(args) {
+            Ok(ColumnarValue::Scalar(ScalarValue::try_from_array(
+                &array, 0,
+            )?))
+        } else {
+            Ok(ColumnarValue::Array(array))
+        }
+    });
+
+    Ok(create_udf(
+        &definition.name,
+        definition
+            .params
+            .iter()
+            .map(|param| param.data_type.clone())
+            .collect(),
+        Arc::new(definition.return_type.clone()),
+        Volatility::Immutable,
+        fun,
+    ))
+}
+
+#[cfg(test)]
+mod test_wasm {
+    use datafusion::{arrow::datatypes::Int64Type, prelude::SessionContext};
+
+    use crate::query::functions::{FunctionLanguage, FunctionParam};
+
+    use super::*;
+
+    /// exports `add_one`, which adds one to an i64, and `spin`, which never returns
+    const MODULE: &[u8] = &[
+        0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // header
+        0x01, 0x06, 0x01, 0x60, 0x01, 0x7e, 0x01, 0x7e, // type (i64) -> i64
+        0x03, 0x03, 0x02, 0x00, 0x00, // two functions of that type
+        0x07, 0x12, 0x02, // exports
+        0x07, b'a', b'd', b'd', b'_', b'o', b'n', b'e', 0x00, 0x00, //
+        0x04, b's', b'p', b'i', b'n', 0x00, 0x01, //
+        0x0a, 0x13, 0x02, // code
+        0x07, 0x00, 0x20, 0x00, 0x42, 0x01, 0x7c, 0x0b, // local.get 0, i64.const 1, i64.add
+        0x09, 0x00, 0x03, 0x40, 0x0c, 0x00, 0x0b, 0x20, 0x00,
+        0x0b, // loop br 0 end, local.get 0
+    ];
+
+    fn modules_dir() -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("file-fusion-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("math.wasm"), MODULE).unwrap();
+        dir
+    }
+
+    fn definition(name: &str, data_type: DataType) -> FunctionDefinition {
+        FunctionDefinition {
+            name: String::from(name),
+            params: vec![FunctionParam {
+                name: String::from("x"),
+                data_type: data_type.clone(),
+            }],
+            return_type: data_type,
+            body: String::from("math.wasm"),
+            language: FunctionLanguage::Wasm,
+        }
+    }
+
+    async fn query(udf: ScalarUDF, sql: &str) -> Result<Vec<Option<i64>>, DataFusionError> {
+        let ctx = SessionContext::new();
+        ctx.register_udf(udf);
+        let batches = ctx.sql(sql).await?.collect().await?;
+
+        Ok(batches
+            .iter()
+            .flat_map(|batch| batch.column(0).as_primitive::<Int64Type>().iter())
+            .collect())
+    }
+
+    #[tokio::test]
+    async fn calls_exported_function() {
+        let dir = modules_dir();
+        let udf = compile(&definition("add_one", DataType::Int64), &dir).unwrap();
+
+        let values = query(
+            udf,
+            "SELECT add_one(column1) FROM (VALUES (1), (NULL), (41))",
+        )
+        .await
+        .unwrap();
+        assert_eq!(values, vec![Some(2), None, Some(42)]);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn fails_when_out_of_fuel() {
+        let dir = modules_dir();
+        let udf = compile(&definition("spin", DataType::Int64), &dir).unwrap();
+
+        let error = query(udf, "SELECT spin(column1) FROM (VALUES (1))")
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("fuel"), "{}", error);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn rejects_mismatched_signature() {
+        let dir = modules_dir();
+
+        let error = compile(&definition("add_one", DataType::Int32), &dir).unwrap_err();
+        assert!(matches!(error, Error::InvalidFunction(_)));
+        let error = compile(&definition("missing", DataType::Int64), &dir).unwrap_err();
+        assert!(matches!(error, Error::InvalidFunction(_)));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,196 @@
+use std::sync::Arc;
+
+use futures::StreamExt;
+use globset::GlobMatcher;
+use object_store::{path::Path, ObjectMeta};
+use regex::Regex;
+use tokio::sync::watch;
+use tracing::{debug, info, warn};
+
+use crate::{content::Content, errors::Error, query::build_glob, state::store::ObjectStore};
+
+/// the name of the event emitted to the window while a search is running
+pub const SEARCH_EVENT: &str = "search";
+
+/// the maximum number of matches sent in a single event
+const MAX_MATCHES_PER_EVENT: usize = 100;
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub enum SearchPattern {
+    Glob(String),
+    Regex(String),
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct SearchRequest {
+    pub prefix: String,
+    pub pattern: SearchPattern,
+    pub min_size: Option<usize>,
+    pub max_size: Option<usize>,
+    /// milliseconds since the unix epoch
+    pub modified_after: Option<i64>,
+    /// milliseconds since the unix epoch
+    pub modified_before: Option<i64>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub enum SearchEvent {
+    Matches { search: usize, items: Vec<Content> },
+    Completed { search: usize, num_matches: usize },
+    Cancelled { search: usize, num_matches: usize },
+    Failed { search: usize, error: String },
+}
+
+#[derive(Debug, Clone)]
+enum Matcher {
+    Glob(GlobMatcher),
+    Regex(Regex),
+}
+
+impl Matcher {
+    fn new(pattern: &SearchPattern) -> Result<Self, Error> {
+        let matcher = match pattern {
+            // the same globs as buffer prefixes, `*` does not cross directories
+            SearchPattern::Glob(glob) => Self::Glob(build_glob(glob)?.compile_matcher()),
+            SearchPattern::Regex(regex) => Self::Regex(Regex::new(regex)?),
+        };
+
+        Ok(matcher)
+    }
+
+    fn is_match(&self, location: &str) -> bool {
+        match self {
+            Self::Glob(glob) => glob.is_match(location),
+            Self::Regex(regex) => regex.is_match(location),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Search {
+    id: usize,
+    prefix: Path,
+    matcher: Matcher,
+    request: SearchRequest,
+    /// set to true to cancel the search
+    cancelled: Arc<watch::Sender<bool>>,
+}
+
+impl Search {
+    pub fn new(id: usize, request: SearchRequest) -> Result<Self, Error> {
+        let prefix = Path::parse(&request.prefix)?;
+        let matcher = Matcher::new(&request.pattern)?;
+
+        Ok(Self {
+            id,
+            prefix,
+            matcher,
+            request,
+            cancelled: Arc::new(watch::channel(false).0),
+        })
+    }
+
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.send_replace(true);
+    }
+
+    fn is_match(&self, meta: &ObjectMeta) -> bool {
+        let request = &self.request;
+        let last_modified = meta.last_modified.timestamp_millis();
+
+        request.min_size.map_or(true, |min| meta.size >= min)
+            && request.max_size.map_or(true, |max| meta.size <= max)
+            && request
+                .modified_after
+                .map_or(true, |after| last_modified >= after)
+            && request
+                .modified_before
+                .map_or(true, |before| last_modified <= before)
+            && self.matcher.is_match(meta.location.as_ref())
+    }
+
+    /// walks the store from the prefix and passes batches of matches to `emit` as they are found
+    #[tracing::instrument(name = "running search", skip(self, store, emit), fields(
+        search = self.id,
+        store = store.metadata.id
+    ))]
+    pub async fn run<F>(&self, store: &ObjectStore, emit: F) -> SearchEvent
+    where
+        F: Fn(SearchEvent),
+    {
+        let mut num_matches = 0;
+        let mut stream = store
+            .client
+            .list(Some(&self.prefix))
+            .ready_chunks(MAX_MATCHES_PER_EVENT);
+
+        let mut cancelled = self.cancelled.subscribe();
+        loop {
+            // a store that is slow to return the next items does not delay cancelling
+            let items = tokio::select! {
+                _ = cancelled.wait_for(|cancelled| *cancelled) => {
+                    info!(num_matches, "search cancelled");
+                    return SearchEvent::Cancelled {
+                        search: self.id,
+                        num_matches,
+                    };
+                }
+                items = stream.next() => match items {
+                    Some(items) => items,
+                    None => break,
+                },
+            };
+
+            let mut matches = Vec::new();
+            for item in items {
+                match item {
+                    Ok(meta) if self.is_match(&meta) => matches.push(Content::from_meta(&meta)),
+                    Ok(_) => {}
+                    Err(e) => {
+                        warn!(?e, "failed to list items in store");
+                        return SearchEvent::Failed {
+                            search: self.id,
+                            error: e.to_string(),
+                        };
+                    }
+                }
+            }
+
+            if !matches.is_empty() {
+                num_matches += matches.len();
+                debug!(num_matches, "found matches");
+                emit(SearchEvent::Matches {
+                    search: self.id,
+                    items: matches,
+                });
+            }
+        }
+
+        info!(num_matches, "search completed");
+        SearchEvent::Completed {
+            search: self.id,
+            num_matches,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_search {
+    use super::*;
+
+    #[test]
+    fn globs_do_not_cross_directories() {
+        let matcher = Matcher::new(&SearchPattern::Glob(String::from("data/*.parquet"))).unwrap();
+
+        assert!(matcher.is_match("data/a.parquet"));
+        assert!(!matcher.is_match("data/year=2023/a.parquet"));
+
+        let matcher =
+            Matcher::new(&SearchPattern::Glob(String::from("data/**/*.parquet"))).unwrap();
+        assert!(matcher.is_match("data/year=2023/a.parquet"));
+    }
+}
@@ -1,28 +1,54 @@
 use datafusion::{
-    arrow::{self, datatypes::Schema},
-    execution::context::{SQLOptions, SessionContext},
+    arrow::{self, datatypes::Schema, record_batch::RecordBatch},
+    dataframe::{DataFrame, DataFrameWriteOptions},
+    execution::{
+        cache::{cache_manager::CacheManagerConfig, CacheAccessor},
+        context::{SQLOptions, SessionContext},
+    },
+    logical_expr::ScalarUDF,
+    physical_plan::execute_stream,
 };
-use futures::lock::Mutex;
+use futures::{future::BoxFuture, lock::Mutex, FutureExt, TryStreamExt};
 use object_store::path::Path;
 use serde_json::{Map, Value};
 use tracing::{debug, info, warn};
 
 use crate::{
+    content::{ContentPage, ContentSort, Listing, PageRequest, PageToken},
     errors::Error,
-    events::{store, Events},
-    query::{Buffer, FileSystemBuffer, Query},
+    events::{buffer, store, Events},
+    messages::buffer::{BufferMetadata, FileSystemBufferMetadata, Materialize},
+    profile::{BufferProfile, ProfileEvent, ProfileJob, ProfileRequest},
+    query::{
+        diff::{DiffRequest, DiffSource, RowDiff, DEFAULT_DIFF_SAMPLE},
+        explain::Explanation,
+        functions::{dependents, FunctionDefinition},
+        naming::{view_names, BufferTables, TableName},
+        results::IpcResult,
+        schemas::{CachedSchema, SchemaCache, SchemaComparison, SchemaOverride},
+        summary::ResultSummary,
+        Buffer, FileSystemBuffer, PinCache, PrefixPattern, Query,
+    },
+    search::{Search, SearchRequest},
+    tracing::{set_log_level, LogFilterHandle},
 };
 
 use std::{
     cmp::Ordering,
+    collections::{hash_map::DefaultHasher, HashMap},
     fmt,
     fs::{create_dir_all, OpenOptions},
+    hash::{Hash, Hasher},
     io::{prelude::*, BufReader},
     path::PathBuf,
     sync::Arc,
+    time::{Duration, Instant, SystemTime},
 };
 
 use super::{
+    listing_cache::ListingCache,
+    runtime::RuntimeSettings,
+    settings::Settings,
     store::{get_home_dir, Connection, LocalConnection, Metadata, ObjectStore, ObjectStoreKind},
     table::Table,
     Id, MutexMap,
@@ -32,7 +58,7 @@ use super::{
 pub struct PrefixState {
     id: usize,
     file_system_buffer: usize,
-    path: Path,
+    pattern: PrefixPattern,
 }
 
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
@@ -43,12 +69,22 @@ pub struct FileSystemBufferState {
     prefixes: Vec<usize>,
 }
 
-#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, Eq)]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, PartialEq, Eq)]
+pub enum BufferKind {
+    Files,
+    View { statement: String },
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct BufferState {
     id: usize,
     name: String,
+    kind: BufferKind,
     common_schema: bool,
     file_systems: Vec<usize>,
+    schema_override: SchemaOverride,
+    as_of: Option<i64>,
+    listing_ttl_secs: Option<u64>,
 }
 
 impl Ord for BufferState {
@@ -69,6 +105,30 @@ impl PartialEq for BufferState {
     }
 }
 
+impl Eq for BufferState {}
+
+#[derive(Debug, Clone)]
+pub struct FunctionState {
+    id: usize,
+    definition: FunctionDefinition,
+    udf: ScalarUDF,
+}
+
+#[derive(Debug, Clone)]
+pub struct RegistrationState {
+    buffer: usize,
+    key: u64,
+    tables: BufferTables,
+    expires_at: Instant,
+}
+
+/// a listing that is continued by page tokens, the least recently used listings are dropped
+#[derive(Debug, Clone)]
+pub struct ListingState {
+    listing: Arc<Mutex<Listing>>,
+    last_used: Instant,
+}
+
 #[derive(Debug, Clone)]
 pub struct State {
     event_id: Id,
@@ -76,7 +136,15 @@ pub struct State {
     buffers: MutexMap<BufferState>,
     file_system_buffers: MutexMap<FileSystemBufferState>,
     prefixes: MutexMap<PrefixState>,
-    schemas: MutexMap<Arc<Mutex<Option<Arc<Schema>>>>>,
+    schemas: MutexMap<SchemaCache>,
+    pins: MutexMap<PinCache>,
+    listings: MutexMap<ListingState>,
+    searches: MutexMap<Search>,
+    /// the latest profile of each buffer
+    profiles: MutexMap<BufferProfile>,
+    profile_jobs: MutexMap<ProfileJob>,
+    functions: MutexMap<FunctionState>,
+    registrations: MutexMap<RegistrationState>,
 }
 
 impl State {
@@ -88,6 +156,13 @@ impl State {
             file_system_buffers: MutexMap::new(),
             prefixes: MutexMap::new(),
             schemas: MutexMap::new(),
+            pins: MutexMap::new(),
+            listings: MutexMap::new(),
+            searches: MutexMap::new(),
+            profiles: MutexMap::new(),
+            profile_jobs: MutexMap::new(),
+            functions: MutexMap::new(),
+            registrations: MutexMap::new(),
         }
     }
 }
@@ -95,12 +170,25 @@ impl State {
 #[derive(Debug, Clone)]
 pub struct Config {
     pub events_file: PathBuf,
+    pub settings_file: PathBuf,
+    /// the runtime settings file of earlier versions, migrated into the settings file on start
+    pub runtime_file: PathBuf,
+    /// the webassembly modules functions are loaded from
+    pub functions_dir: PathBuf,
+    /// applies changes of the log level, the level is fixed when not set
+    pub log_filter: Option<LogFilterHandle>,
 }
 
 pub struct App {
     config: Config,
     state: State,
-    session: SessionContext,
+    /// replaced when the runtime settings change
+    session: Mutex<SessionContext>,
+    settings: Mutex<Settings>,
+    /// the modification time of the settings file when it was last read or written
+    settings_modified: Mutex<Option<SystemTime>>,
+    list_files_cache: Arc<ListingCache>,
+    registration_lock: Mutex<()>,
 }
 
 impl fmt::Debug for App {
@@ -110,12 +198,139 @@ impl fmt::Debug for App {
 }
 
 impl App {
-    pub fn new(config: Config) -> Self {
-        Self {
+    pub fn new(config: Config) -> Result<Self, Error> {
+        if let Err(e) = Settings::migrate_runtime_file(&config.settings_file, &config.runtime_file)
+        {
+            warn!(?e, "failed to migrate runtime settings");
+        }
+
+        let settings = match Settings::load(&config.settings_file) {
+            Ok(settings) => settings,
+            Err(e) => {
+                warn!(?e, "failed to load settings, using defaults");
+                Settings::default()
+            }
+        };
+        let settings_modified = modified(&config.settings_file);
+
+        let list_files_cache = Arc::new(ListingCache::new(settings.cache.listing_ttl()));
+        let cache_config =
+            CacheManagerConfig::default().with_list_files_cache(Some(list_files_cache.clone()));
+        let session = settings.runtime.build_session(cache_config)?;
+
+        Ok(Self {
             config,
             state: State::new(),
-            session: SessionContext::new(),
+            session: Mutex::new(session),
+            settings: Mutex::new(settings),
+            settings_modified: Mutex::new(settings_modified),
+            list_files_cache,
+            registration_lock: Mutex::new(()),
+        })
+    }
+
+    async fn session(&self) -> SessionContext {
+        self.session.lock().await.clone()
+    }
+
+    pub async fn get_settings(&self) -> Settings {
+        self.settings.lock().await.clone()
+    }
+
+    /// validates, applies and saves the settings
+    #[tracing::instrument(name = "updating settings", skip(self))]
+    pub async fn update_settings(&self, settings: Settings) -> Result<(), Error> {
+        settings.validate()?;
+        self.apply_settings(settings.clone()).await?;
+
+        settings.save(&self.config.settings_file)?;
+        *self.settings_modified.lock().await = modified(&self.config.settings_file);
+
+        Ok(())
+    }
+
+    pub async fn get_runtime_settings(&self) -> RuntimeSettings {
+        self.get_settings().await.runtime
+    }
+
+    /// replaces the runtime settings and saves them with the other settings
+    pub async fn update_runtime_settings(&self, runtime: RuntimeSettings) -> Result<(), Error> {
+        let mut settings = self.get_settings().await;
+        settings.runtime = runtime;
+
+        self.update_settings(settings).await
+    }
+
+    /// applies the settings file if it was changed since it was last read or written
+    pub async fn reload_settings(&self) -> Result<(), Error> {
+        let current = modified(&self.config.settings_file);
+        {
+            let mut last_modified = self.settings_modified.lock().await;
+            if current.is_none() || *last_modified == current {
+                return Ok(());
+            }
+            *last_modified = current;
         }
+
+        info!("settings file changed, reloading settings");
+        let settings = Settings::load(&self.config.settings_file)?;
+        self.apply_settings(settings).await
+    }
+
+    async fn apply_settings(&self, settings: Settings) -> Result<(), Error> {
+        let previous = self.get_settings().await;
+
+        if settings.runtime != previous.runtime {
+            self.rebuild_session(&settings.runtime).await?;
+        }
+
+        if settings.cache != previous.cache {
+            self.list_files_cache
+                .set_default_ttl(settings.cache.listing_ttl());
+            self.list_files_cache.clear();
+
+            let _lock = self.registration_lock.lock().await;
+            for registration in self.state.registrations.list().await {
+                self.state.registrations.remove(registration.buffer).await;
+            }
+            info!("cache settings changed, cleared cached listings");
+        }
+
+        if settings.log_level != previous.log_level {
+            if let Some(log_filter) = &self.config.log_filter {
+                set_log_level(log_filter, &settings.log_level)?;
+                info!(log_level = %settings.log_level, "changed log level");
+            }
+        }
+
+        *self.settings.lock().await = settings;
+
+        Ok(())
+    }
+
+    /// rebuilds the session with the new runtime settings, the object stores are registered with
+    /// the new session and buffers are registered again when they are next queried
+    #[tracing::instrument(name = "rebuilding session", skip(self))]
+    async fn rebuild_session(&self, runtime: &RuntimeSettings) -> Result<(), Error> {
+        let cache_config = CacheManagerConfig::default()
+            .with_list_files_cache(Some(self.list_files_cache.clone()));
+        let session = runtime.build_session(cache_config)?;
+
+        let _lock = self.registration_lock.lock().await;
+        for mut store in self.state.stores.list().await {
+            store.reregister(&session)?;
+            self.state.stores.insert(store.metadata.id, store).await;
+        }
+        for registration in self.state.registrations.list().await {
+            self.state.registrations.remove(registration.buffer).await;
+        }
+        for function in self.state.functions.list().await {
+            session.register_udf(function.udf);
+        }
+
+        *self.session.lock().await = session;
+
+        Ok(())
     }
 
     #[tracing::instrument(name = "listing buffers", skip(self))]
@@ -162,6 +377,219 @@ impl App {
         self.state.stores.list().await
     }
 
+    #[tracing::instrument(name = "listing contents page", skip(self))]
+    pub async fn list_contents(
+        &self,
+        store_id: &usize,
+        prefix: String,
+        request: &PageRequest,
+    ) -> Result<ContentPage, Error> {
+        let page_size = request.page_size();
+
+        if let Some(token) = &request.token {
+            let token = PageToken::parse(token)?;
+            let state = self
+                .state
+                .listings
+                .get(&token.listing)
+                .await
+                .ok_or(Error::NotFound(format!(
+                    "listing {} has expired",
+                    token.listing
+                )))?;
+            self.cache_listing(token.listing, state.listing.clone())
+                .await;
+
+            let mut listing = state.listing.lock().await;
+            return listing.page(token.listing, token.offset, page_size).await;
+        }
+
+        let store = self
+            .get_store(store_id)
+            .await
+            .ok_or(Error::NotFound(format!(
+                "object store with id {}",
+                store_id
+            )))?;
+
+        let prefix = if prefix.is_empty() {
+            store.metadata.prefix.clone()
+        } else {
+            prefix
+        };
+
+        let mut contents = Listing::start(store, &prefix, request.filter.as_deref())?;
+        let sort = request.sort.unwrap_or_default();
+        let descending = request.descending.unwrap_or(false);
+        // listings are sorted by name as pages are read, other orders need every item
+        if !matches!(sort, ContentSort::Name) || descending {
+            contents.sort(sort, descending).await?;
+        }
+
+        let listing = self.state.listings.get_id().await;
+        let page = contents.page(listing, 0, page_size).await?;
+
+        if page.next_token.is_some() {
+            self.cache_listing(listing, Arc::new(Mutex::new(contents)))
+                .await;
+        }
+
+        debug!(listing, total = ?page.total, "listed contents");
+
+        Ok(page)
+    }
+
+    /// marks a listing as used and drops the least recently used listings above the limit
+    async fn cache_listing(&self, id: usize, listing: Arc<Mutex<Listing>>) {
+        let state = ListingState {
+            listing,
+            last_used: Instant::now(),
+        };
+        self.state.listings.insert(id, state).await;
+
+        let max_listings = self.get_settings().await.cache.max_listings;
+        let mut listings = self.state.listings.entries().await;
+        if listings.len() <= max_listings {
+            return;
+        }
+
+        listings.sort_by_key(|(_, state)| state.last_used);
+        let num_evicted = listings.len() - max_listings;
+        for (id, _) in listings.into_iter().take(num_evicted) {
+            self.state.listings.remove(id).await;
+            debug!(listing = id, "evicted listing");
+        }
+    }
+
+    #[tracing::instrument(name = "starting search", skip(self))]
+    pub async fn start_search(&self, request: SearchRequest) -> Result<Search, Error> {
+        let id = self.state.searches.get_id().await;
+        let search = Search::new(id, request)?;
+        self.state.searches.insert(id, search.clone()).await;
+
+        Ok(search)
+    }
+
+    #[tracing::instrument(name = "cancelling search", skip(self))]
+    pub async fn cancel_search(&self, id: &usize) -> Result<(), Error> {
+        let search = self
+            .state
+            .searches
+            .get(id)
+            .await
+            .ok_or(Error::NotFound(format!("search with id {}", id)))?;
+
+        search.cancel();
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "finishing search", skip(self))]
+    pub async fn finish_search(&self, id: usize) {
+        self.state.searches.remove(id).await;
+    }
+
+    #[tracing::instrument(name = "starting profile", skip(self))]
+    pub async fn start_profile(&self, request: ProfileRequest) -> Result<ProfileJob, Error> {
+        self.get_buffer(&request.buffer).await?;
+
+        let id = self.state.profile_jobs.get_id().await;
+        let job = ProfileJob::new(id, request);
+        self.state.profile_jobs.insert(id, job.clone()).await;
+
+        Ok(job)
+    }
+
+    #[tracing::instrument(name = "cancelling profile", skip(self))]
+    pub async fn cancel_profile(&self, id: &usize) -> Result<(), Error> {
+        let job = self
+            .state
+            .profile_jobs
+            .get(id)
+            .await
+            .ok_or(Error::NotFound(format!("profile with id {}", id)))?;
+
+        job.cancel();
+        Ok(())
+    }
+
+    /// runs a profile job and saves the profile, returns the event to emit when the job is done
+    pub async fn run_profile<F>(&self, job: &ProfileJob, emit: F) -> ProfileEvent
+    where
+        F: Fn(ProfileEvent),
+    {
+        let result = self.profile(job, emit).await;
+        self.state.profile_jobs.remove(job.id()).await;
+
+        match result {
+            Ok(Some(profile)) => {
+                let buffer = profile.buffer;
+                let event_id = self.next_event_id().await;
+                let event = Events::SaveBufferProfile(buffer::SaveProfile {
+                    id: event_id,
+                    profile,
+                });
+
+                match self.save(&event).await {
+                    Ok(()) => ProfileEvent::Completed {
+                        profile: job.id(),
+                        buffer,
+                    },
+                    Err(e) => ProfileEvent::Failed {
+                        profile: job.id(),
+                        error: e.to_string(),
+                    },
+                }
+            }
+            Ok(None) => ProfileEvent::Cancelled { profile: job.id() },
+            Err(e) => {
+                warn!(?e, profile = job.id(), "failed to profile buffer");
+                ProfileEvent::Failed {
+                    profile: job.id(),
+                    error: e.to_string(),
+                }
+            }
+        }
+    }
+
+    async fn profile<F>(&self, job: &ProfileJob, emit: F) -> Result<Option<BufferProfile>, Error>
+    where
+        F: Fn(ProfileEvent),
+    {
+        let buffer = self.get_buffer(&job.buffer()).await?;
+        let df = self.buffer_frame(&buffer).await?;
+        let files = buffer.list_files().await?;
+
+        job.run(files, df, emit).await
+    }
+
+    /// registers a buffer and returns its view as a dataframe
+    async fn buffer_frame(&self, buffer: &Buffer) -> Result<DataFrame, Error> {
+        self.register_with_dependencies(buffer, Vec::new()).await?;
+
+        let tables = self.get_buffer_tables(&buffer.id).await?;
+        let df = self
+            .session()
+            .await
+            .table(tables.view.table_reference())
+            .await?;
+
+        Ok(df)
+    }
+
+    pub async fn list_functions(&self) -> Vec<FunctionDefinition> {
+        let mut functions = self.state.functions.list().await;
+        functions.sort_by_key(|function| function.id);
+
+        functions
+            .into_iter()
+            .map(|function| function.definition)
+            .collect()
+    }
+
+    pub async fn get_profile(&self, buffer_id: &usize) -> Option<BufferProfile> {
+        self.state.profiles.get(buffer_id).await
+    }
+
     #[tracing::instrument(name = "syncing app state", skip(self))]
     pub async fn sync(&self) -> Result<(), Error> {
         let events_file = self.config.events_file.clone();
@@ -184,6 +612,13 @@ impl App {
             let line = line?;
             let event = serde_json::from_str::<Events>(&line);
             match event {
+                // a function that no longer compiles, e.g. because a function it calls changed,
+                // should not keep the app from starting
+                Ok(event @ Events::CreateFunction(_)) => {
+                    if let Err(e) = self.update(&event).await {
+                        warn!(?e, ?event, "failed to compile function, skipping it");
+                    }
+                }
                 Ok(event) => self.update(&event).await?,
                 Err(_) => return Err(Error::FailedToDeserializeEvents),
             }
@@ -241,7 +676,7 @@ impl App {
                 let connection = event.connection.clone();
 
                 let mut store = ObjectStore::new(metadata, connection)?;
-                store.register(&self.session)?;
+                store.register(&self.session().await)?;
                 self.state.event_id.update(event.id).await;
                 self.state.stores.insert(id, store).await;
             }
@@ -253,8 +688,12 @@ impl App {
                 let mut buffer_state = BufferState {
                     id: buffer_id,
                     name: metadata.name.clone(),
+                    kind: BufferKind::Files,
                     common_schema: metadata.common_schema,
                     file_systems: Vec::new(),
+                    schema_override: metadata.schema_override,
+                    as_of: metadata.as_of,
+                    listing_ttl_secs: metadata.listing_ttl_secs,
                 };
 
                 for item in metadata.file_systems.into_iter() {
@@ -263,12 +702,12 @@ impl App {
 
                     let mut prefix_ids = Vec::new();
                     for prefix in item.prefixes {
-                        let path = Path::parse(prefix)?;
+                        let pattern = PrefixPattern::parse(&prefix)?;
                         let prefix_id = self.state.prefixes.get_id().await;
                         let prefix_state = PrefixState {
                             id: prefix_id,
                             file_system_buffer: file_system_buffer_id,
-                            path,
+                            pattern,
                         };
                         prefix_ids.push(prefix_state.id);
                         self.state
@@ -297,12 +736,144 @@ impl App {
                     .insert(buffer_state.id, buffer_state)
                     .await;
             }
+            Events::CreateView(event) => {
+                self.state.event_id.update(event.id).await;
+
+                let buffer_id = self.next_buffer_id().await;
+                let buffer_state = BufferState {
+                    id: buffer_id,
+                    name: event.name.clone(),
+                    kind: BufferKind::View {
+                        statement: event.statement.clone(),
+                    },
+                    common_schema: true,
+                    file_systems: Vec::new(),
+                    schema_override: SchemaOverride::default(),
+                    as_of: None,
+                    listing_ttl_secs: None,
+                };
+
+                self.state
+                    .buffers
+                    .insert(buffer_state.id, buffer_state)
+                    .await;
+            }
+            Events::CacheBufferSchema(event) => {
+                self.state.event_id.update(event.id).await;
+
+                let cache = self.get_schema(&event.buffer).await;
+                let mut lock = cache.lock().await;
+                *lock = Some(CachedSchema {
+                    schema: event.schema.clone(),
+                    fingerprint: event.fingerprint.clone(),
+                    persisted: true,
+                    checked_at: None,
+                });
+            }
+            Events::SaveBufferProfile(event) => {
+                self.state.event_id.update(event.id).await;
+
+                self.state
+                    .profiles
+                    .insert(event.profile.buffer, event.profile.clone())
+                    .await;
+            }
+            Events::CreateFunction(event) => {
+                self.state.event_id.update(event.id).await;
+
+                let name = &event.definition.name;
+                let (replaced, mut functions): (Vec<FunctionState>, Vec<FunctionState>) = self
+                    .state
+                    .functions
+                    .list()
+                    .await
+                    .into_iter()
+                    .partition(|function| &function.definition.name == name);
+
+                let udfs: Vec<ScalarUDF> = functions
+                    .iter()
+                    .map(|function| function.udf.clone())
+                    .collect();
+                let udf = event
+                    .definition
+                    .compile(&udfs, &self.config.functions_dir)
+                    .await?;
+                functions.push(FunctionState {
+                    id: event.id,
+                    definition: event.definition.clone(),
+                    udf,
+                });
+
+                // the functions calling the new function still run the body they were compiled
+                // with, they are compiled again before anything is replaced
+                let definitions: Vec<(usize, &FunctionDefinition)> = functions
+                    .iter()
+                    .map(|function| (function.id, &function.definition))
+                    .collect();
+                let dependents = dependents(&definitions, name);
+                for id in dependents.iter() {
+                    let udfs: Vec<ScalarUDF> = functions
+                        .iter()
+                        .filter(|function| function.id != *id)
+                        .map(|function| function.udf.clone())
+                        .collect();
+                    if let Some(function) = functions.iter_mut().find(|function| function.id == *id)
+                    {
+                        function.udf = function
+                            .definition
+                            .compile(&udfs, &self.config.functions_dir)
+                            .await?;
+                        debug!(function = %function.definition.name, "recompiled dependent function");
+                    }
+                }
+
+                for function in replaced {
+                    self.state.functions.remove(function.id).await;
+                }
+                let session = self.session().await;
+                for function in functions {
+                    if function.id == event.id || dependents.contains(&function.id) {
+                        session.register_udf(function.udf.clone());
+                        self.state.functions.insert(function.id, function).await;
+                    }
+                }
+            }
+            Events::UpdateBufferSchema(event) => {
+                let mut buffer_state = self
+                    .state
+                    .buffers
+                    .get(&event.buffer)
+                    .await
+                    .ok_or(Error::NotFound(format!("buffer with id {}", event.buffer)))?;
+                self.state.event_id.update(event.id).await;
+
+                buffer_state.schema_override = event.schema_override.clone();
+                self.state
+                    .buffers
+                    .insert(buffer_state.id, buffer_state)
+                    .await;
+            }
+            Events::PinBufferVersions(event) => {
+                let mut buffer_state = self
+                    .state
+                    .buffers
+                    .get(&event.buffer)
+                    .await
+                    .ok_or(Error::NotFound(format!("buffer with id {}", event.buffer)))?;
+                self.state.event_id.update(event.id).await;
+
+                buffer_state.as_of = event.as_of;
+                self.state
+                    .buffers
+                    .insert(buffer_state.id, buffer_state)
+                    .await;
+            }
         }
 
         Ok(())
     }
 
-    async fn get_schema(&self, buffer_id: &usize) -> Arc<Mutex<Option<Arc<Schema>>>> {
+    async fn get_schema(&self, buffer_id: &usize) -> SchemaCache {
         let schema = self
             .state
             .schemas
@@ -311,6 +882,50 @@ impl App {
         schema
     }
 
+    async fn get_pins(&self, buffer_id: &usize) -> PinCache {
+        self.state
+            .pins
+            .get_or_insert(buffer_id, || Arc::new(Mutex::new(None)))
+            .await
+    }
+
+    /// pinned buffers read object versions, which only remote stores keep
+    pub async fn check_versioned(&self, buffer_id: &usize) -> Result<(), Error> {
+        let buffer_state = self
+            .state
+            .buffers
+            .get(buffer_id)
+            .await
+            .ok_or(Error::NotFound(format!("buffer with id {}", buffer_id)))?;
+
+        for file_system_id in buffer_state.file_systems {
+            let file_system = self
+                .state
+                .file_system_buffers
+                .get(&file_system_id)
+                .await
+                .ok_or(Error::NotFound(format!(
+                    "file system buffer with id {}",
+                    file_system_id
+                )))?;
+            let store = self
+                .state
+                .stores
+                .get(&file_system.store)
+                .await
+                .ok_or(Error::NotFound(format!(
+                    "store with id {}",
+                    file_system.store
+                )))?;
+
+            if let Connection::Local(_) = store.connection {
+                return Err(Error::UnversionedStore(store.metadata.name));
+            }
+        }
+
+        Ok(())
+    }
+
     #[tracing::instrument(name = "getting buffer", skip(self))]
     async fn get_buffer(&self, buffer_id: &usize) -> Result<Buffer, Error> {
         let buffer_state = self
@@ -331,8 +946,21 @@ impl App {
             files_system_buffer_states.push(file_system);
         }
 
+        // the cache setting applies to buffers without a listing ttl of their own
+        let listing_ttl = match buffer_state.listing_ttl_secs {
+            Some(secs) => Duration::from_secs(secs),
+            None => self.get_settings().await.cache.listing_ttl(),
+        };
         let schema = self.get_schema(buffer_id).await;
-        let mut buffer = Buffer::new(&buffer_state.id, &buffer_state.name, schema);
+        let mut buffer = Buffer::new(
+            &buffer_state.id,
+            &buffer_state.name,
+            schema,
+            buffer_state.schema_override.clone(),
+        )
+        .with_as_of(buffer_state.as_of)
+        .with_pin_cache(self.get_pins(buffer_id).await)
+        .with_listing_ttl(Some(listing_ttl));
 
         for file_system_buffer_state in files_system_buffer_states {
             let store = self
@@ -345,7 +973,7 @@ impl App {
                     &file_system_buffer_state.store
                 )))?;
 
-            let mut paths = Vec::new();
+            let mut patterns = Vec::new();
 
             for prefix in file_system_buffer_state.prefixes {
                 let prefix = self
@@ -355,14 +983,18 @@ impl App {
                     .await
                     .ok_or(Error::NotFound(format!("prefix with id {}", prefix)))?;
 
-                paths.push(prefix.path);
+                patterns.push(prefix.pattern);
             }
 
-            let file_system_buffer = FileSystemBuffer::new(store, &paths);
+            let file_system_buffer = FileSystemBuffer::new(store, &patterns);
 
             buffer.insert(file_system_buffer);
         }
 
+        if let BufferKind::View { statement } = buffer_state.kind {
+            buffer = buffer.with_definition(statement);
+        }
+
         Ok(buffer)
     }
 
@@ -372,39 +1004,518 @@ impl App {
     pub async fn get_table(&self, table: &usize) -> Result<Table, Error> {
         let buffer = self.get_buffer(table).await?;
 
-        let schema = buffer.get_schema(&self.session.state()).await?;
+        if buffer.definition().is_some() {
+            // the buffers used by the view have to be registered to derive its schema
+            self.register_with_dependencies(&buffer, Vec::new()).await?;
+        }
+
+        let schema = buffer.get_schema(&self.session().await.state()).await?;
+        self.persist_schema(&buffer).await?;
 
         Ok(Table::new(buffer.get_name(), schema))
     }
 
+    #[tracing::instrument(name = "refreshing buffer schema", skip(self))]
+    pub async fn refresh_schema(&self, buffer_id: &usize) -> Result<Table, Error> {
+        {
+            let cache = self.get_schema(buffer_id).await;
+            let mut lock = cache.lock().await;
+            *lock = None;
+        }
+
+        if let Some(registration) = self.state.registrations.remove(*buffer_id).await {
+            self.deregister(&registration).await?;
+        }
+
+        self.get_table(buffer_id).await
+    }
+
+    /// saves a newly inferred schema so it does not have to be inferred again after a restart
+    async fn persist_schema(&self, buffer: &Buffer) -> Result<(), Error> {
+        let cached = match buffer.unpersisted_schema().await {
+            Some(cached) => cached,
+            None => return Ok(()),
+        };
+
+        let event_id = self.next_event_id().await;
+        let event = Events::CacheBufferSchema(buffer::CacheSchema {
+            id: event_id,
+            buffer: buffer.get_id(),
+            fingerprint: cached.fingerprint,
+            schema: cached.schema,
+        });
+
+        self.save(&event).await
+    }
+
+    #[tracing::instrument(name = "comparing buffer schemas", skip(self))]
+    pub async fn compare_schemas(
+        &self,
+        buffer_id: &usize,
+        sample: Option<usize>,
+    ) -> Result<SchemaComparison, Error> {
+        let buffer = self.get_buffer(buffer_id).await?;
+
+        buffer
+            .compare_schemas(&self.session().await.state(), sample)
+            .await
+    }
+
+    /// registers the buffers a view depends on before the view itself, `visited` holds the views
+    /// being registered to break cycles
+    fn register_with_dependencies<'a>(
+        &'a self,
+        buffer: &'a Buffer,
+        mut visited: Vec<usize>,
+    ) -> BoxFuture<'a, Result<(), Error>> {
+        async move {
+            visited.push(buffer.get_id());
+
+            let mut dependencies = Vec::new();
+            if let Some(statement) = buffer.definition() {
+                for buffer_id in self.referenced_buffers(statement).await? {
+                    if visited.contains(&buffer_id) {
+                        warn!(buffer = buffer_id, "skipping circular view dependency");
+                        continue;
+                    }
+
+                    let dependency = self.get_buffer(&buffer_id).await?;
+                    self.register_with_dependencies(&dependency, visited.clone())
+                        .await?;
+                    dependencies.push(buffer_id);
+                }
+            }
+
+            self.register(buffer, &dependencies).await
+        }
+        .boxed()
+    }
+
+    /// registers the tables for a buffer unless they are already registered for the same prefixes,
+    /// stores, schema and dependencies and the listing cache ttl has not expired
+    #[tracing::instrument(name = "registering buffer", skip(self, buffer), fields(
+        buffer = buffer.get_id()
+    ))]
+    async fn register(&self, buffer: &Buffer, dependencies: &[usize]) -> Result<(), Error> {
+        let _lock = self.registration_lock.lock().await;
+
+        // a view has to be registered again when any of its dependencies were
+        let mut hasher = DefaultHasher::new();
+        buffer.registration_key().hash(&mut hasher);
+        for dependency in dependencies {
+            if let Some(registration) = self.state.registrations.get(dependency).await {
+                registration.key.hash(&mut hasher);
+                registration.expires_at.hash(&mut hasher);
+            }
+        }
+        let key = hasher.finish();
+
+        if let Some(registration) = self.state.registrations.get(&buffer.get_id()).await {
+            if registration.key == key && registration.expires_at > Instant::now() {
+                debug!("buffer is already registered");
+                return Ok(());
+            }
+
+            debug!("buffer registration is stale, re-registering");
+            self.deregister(&registration).await?;
+        }
+
+        let ttl = match buffer.listing_ttl() {
+            Some(ttl) => ttl,
+            None => self.get_settings().await.cache.listing_ttl(),
+        };
+        self.list_files_cache.release(buffer.get_id());
+        for (store, prefix) in buffer.listing_prefixes().await? {
+            self.list_files_cache
+                .claim(buffer.get_id(), &store, prefix, ttl);
+        }
+
+        let view = self.view_name(buffer).await;
+        let tables = buffer.register(&view, &self.session().await).await?;
+        debug!(num_tables = tables.prefixes.len(), "registered buffer");
+
+        let registration = RegistrationState {
+            buffer: buffer.get_id(),
+            key,
+            tables,
+            expires_at: Instant::now() + ttl,
+        };
+        self.state
+            .registrations
+            .insert(registration.buffer, registration)
+            .await;
+
+        Ok(())
+    }
+
+    async fn deregister(&self, registration: &RegistrationState) -> Result<(), Error> {
+        let session = self.session().await;
+        let tables = &registration.tables;
+        for name in tables.names() {
+            session.deregister_table(name.table_reference())?;
+        }
+        self.list_files_cache.release(registration.buffer);
+
+        debug!(
+            buffer = registration.buffer,
+            num_tables = tables.prefixes.len(),
+            "deregistered buffer"
+        );
+        Ok(())
+    }
+
+    /// the name the view of a buffer is registered as, see `view_names`
+    async fn view_name(&self, buffer: &Buffer) -> String {
+        let buffers = self.state.buffers.list().await;
+        let names = buffers
+            .iter()
+            .map(|other| (other.id, other.name.as_str()))
+            .filter(|(id, _)| *id != buffer.get_id())
+            .chain(std::iter::once((buffer.get_id(), buffer.get_name())));
+
+        view_names(names)
+            .remove(&buffer.get_id())
+            .unwrap_or_else(|| buffer.get_name().to_string())
+    }
+
+    /// finds the buffers whose view or prefix tables are referenced in a sql statement
+    #[tracing::instrument(name = "resolving referenced buffers", skip(self))]
+    async fn referenced_buffers(&self, statement: &str) -> Result<Vec<usize>, Error> {
+        let state = self.session().await.state();
+        let dialect = state.config_options().sql_parser.dialect.clone();
+        let statement = state.sql_to_statement(statement, &dialect)?;
+        let references = state.resolve_table_references(&statement)?;
+
+        // the tables of every buffer by table name, resolved once for all references
+        let buffers = self.state.buffers.list().await;
+        let names = view_names(
+            buffers
+                .iter()
+                .map(|buffer| (buffer.id, buffer.name.as_str())),
+        );
+        let catalog = state.config_options().catalog.default_catalog.clone();
+        let mut tables_by_name: HashMap<String, Vec<(TableName, usize)>> = HashMap::new();
+        for buffer in buffers.iter() {
+            let tables = match self.state.registrations.get(&buffer.id).await {
+                Some(registration) => registration.tables,
+                None => self
+                    .get_buffer(&buffer.id)
+                    .await?
+                    .tables(&catalog, &names[&buffer.id]),
+            };
+
+            for name in tables.names() {
+                tables_by_name
+                    .entry(name.table.clone())
+                    .or_default()
+                    .push((name.clone(), buffer.id));
+            }
+        }
+
+        let mut buffer_ids: Vec<usize> = references
+            .iter()
+            .flat_map(|reference| {
+                tables_by_name
+                    .get(reference.table())
+                    .into_iter()
+                    .flatten()
+                    .filter(move |(name, _)| name.matches(reference))
+            })
+            .map(|(_, buffer_id)| *buffer_id)
+            .collect();
+        buffer_ids.sort();
+        buffer_ids.dedup();
+
+        debug!(?buffer_ids, "resolved referenced buffers");
+        Ok(buffer_ids)
+    }
+
+    #[tracing::instrument(name = "getting buffer tables", skip(self))]
+    pub async fn get_buffer_tables(&self, buffer_id: &usize) -> Result<BufferTables, Error> {
+        if let Some(registration) = self.state.registrations.get(buffer_id).await {
+            return Ok(registration.tables);
+        }
+
+        let buffer = self.get_buffer(buffer_id).await?;
+        let view = self.view_name(&buffer).await;
+        let catalog = self
+            .session()
+            .await
+            .state()
+            .config_options()
+            .catalog
+            .default_catalog
+            .clone();
+
+        Ok(buffer.tables(&catalog, &view))
+    }
+
     #[tracing::instrument(name = "executing query", skip(self), fields(
         query = ?query
     ))]
     pub async fn query(&self, query: &Query) -> Result<Vec<Map<String, Value>>, Error> {
-        let buffer = self.get_buffer(&query.buffer).await?;
+        let (_, batches) = self.collect_results(query).await?;
+        let batches: Vec<_> = batches.iter().collect();
 
-        let result = buffer.register(buffer.get_name(), &self.session).await;
-        match result {
-            Ok(tables) => {
-                debug!(num_tables = tables.len(), "registered buffer");
+        let list = arrow::json::writer::record_batches_to_json_rows(&batches[..])?;
+
+        Ok(list)
+    }
+
+    #[tracing::instrument(name = "executing query as arrow ipc", skip(self), fields(
+        query = ?query
+    ))]
+    pub async fn query_ipc(&self, query: &Query) -> Result<IpcResult, Error> {
+        let (schema, batches) = self.collect_results(query).await?;
+
+        IpcResult::new(schema, &batches)
+    }
+
+    /// computes column statistics over every row of the query result, the row limit is ignored
+    #[tracing::instrument(name = "summarizing query", skip(self), fields(
+        query = ?query
+    ))]
+    pub async fn summarize(
+        &self,
+        query: &Query,
+        top_k: Option<usize>,
+    ) -> Result<ResultSummary, Error> {
+        let df = self.execute(query).await?;
+
+        ResultSummary::compute(df, top_k).await
+    }
+
+    /// compares the rows of two buffers or query results with equal keys
+    #[tracing::instrument(name = "diffing rows", skip(self))]
+    pub async fn diff(&self, request: DiffRequest) -> Result<RowDiff, Error> {
+        let old = self.diff_source(&request.old).await?;
+        let new = self.diff_source(&request.new).await?;
+        let sample = request.sample.unwrap_or(DEFAULT_DIFF_SAMPLE);
+
+        RowDiff::compute(old, new, request.keys, sample).await
+    }
+
+    async fn diff_source(&self, source: &DiffSource) -> Result<DataFrame, Error> {
+        match source {
+            DiffSource::Buffer(buffer_id) => {
+                let buffer = self.get_buffer(buffer_id).await?;
+                self.buffer_frame(&buffer).await
             }
-            Err(e) => {
-                warn!(?e, "failed to register buffer");
+            DiffSource::Query(query) => self.execute(query).await,
+        }
+    }
+
+    /// executes a query with the row limit and timeout from the settings
+    async fn collect_results(
+        &self,
+        query: &Query,
+    ) -> Result<(Arc<Schema>, Vec<RecordBatch>), Error> {
+        let settings = self.get_settings().await.query;
+        let results = async {
+            let mut df = self.execute(query).await?;
+
+            if let Some(limit) = query.limit.or(settings.row_limit) {
+                df = df.limit(0, Some(limit))?;
+            }
+            let schema = Arc::new(Schema::from(df.schema()));
+            let batches = df.collect().await?;
+
+            Ok::<_, Error>((schema, batches))
+        };
+
+        match settings.timeout() {
+            Some(timeout) => tokio::time::timeout(timeout, results)
+                .await
+                .map_err(|_| Error::QueryTimeout(timeout.as_secs()))?,
+            None => results.await,
+        }
+    }
+
+    /// plans a query and, when analyzing, executes it to collect the metrics of each operator
+    #[tracing::instrument(name = "explaining query", skip(self), fields(
+        query = ?query
+    ))]
+    pub async fn explain(&self, query: &Query, analyze: bool) -> Result<Explanation, Error> {
+        let df = self.execute(query).await?;
+
+        let logical = df.clone().into_optimized_plan()?;
+        let physical = df.create_physical_plan().await?;
+
+        if analyze {
+            // the batches are dropped as they arrive, only the metrics of the plan are kept
+            let stream = execute_stream(physical.clone(), self.session().await.task_ctx())?;
+            let num_rows = stream
+                .try_fold(0, |num_rows, batch| async move {
+                    Ok(num_rows + batch.num_rows())
+                })
+                .await?;
+            debug!(num_rows, "executed plan");
+        }
+
+        Ok(Explanation::new(&logical, &physical, analyze))
+    }
+
+    /// writes the result of a query as parquet files to an empty prefix and creates a buffer for
+    /// them
+    #[tracing::instrument(name = "materializing query", skip(self), fields(
+        query = ?query
+    ))]
+    pub async fn materialize(&self, query: &Query, target: Materialize) -> Result<(), Error> {
+        let store = self
+            .get_store(&target.store)
+            .await
+            .ok_or(Error::NotFound(format!(
+                "object store with id {}",
+                target.store
+            )))?;
+
+        let prefix = Path::parse(&target.prefix)?;
+        let existing = store.client.list_with_delimiter(Some(&prefix)).await?;
+        if !existing.objects.is_empty() || !existing.common_prefixes.is_empty() {
+            return Err(Error::PrefixNotEmpty(prefix.to_string()));
+        }
+
+        let df = self.execute(query).await?;
+
+        let mut path = store.table_path(prefix.as_ref());
+        if !path.ends_with('/') {
+            path.push('/');
+        }
+        let options = DataFrameWriteOptions::new().with_single_file_output(false);
+        df.write_parquet(&path, options, None).await?;
+        info!(path = %path, "wrote query results");
+
+        let event_id = self.next_event_id().await;
+        let event = Events::CreateBuffer(buffer::Create {
+            id: event_id,
+            metadata: BufferMetadata {
+                name: target.name,
+                common_schema: true,
+                file_systems: vec![FileSystemBufferMetadata {
+                    store: target.store,
+                    prefixes: vec![prefix.to_string()],
+                }],
+                schema_override: SchemaOverride::default(),
+                as_of: None,
+                listing_ttl_secs: None,
+            },
+        });
+
+        self.save(&event).await
+    }
+
+    /// registers the buffers used by the query and plans it with the query parameters
+    async fn execute(&self, query: &Query) -> Result<DataFrame, Error> {
+        let mut buffer_ids = self.referenced_buffers(&query.statement).await?;
+        if let Some(buffer_id) = query.buffer {
+            if !buffer_ids.contains(&buffer_id) {
+                buffer_ids.push(buffer_id);
+            }
+        }
+
+        for buffer_id in buffer_ids {
+            let buffer = self.get_buffer(&buffer_id).await?;
+
+            let result = self.register_with_dependencies(&buffer, Vec::new()).await;
+            if let Err(e) = result {
+                warn!(?e, buffer = buffer_id, "failed to register buffer");
+            }
+
+            if let Err(e) = self.persist_schema(&buffer).await {
+                warn!(?e, buffer = buffer_id, "failed to persist buffer schema");
             }
         }
 
-        let state = self.session.state();
+        let session = self.session().await;
+        let state = session.state();
         let plan = state.create_logical_plan(&query.statement).await?;
         let sql_options = SQLOptions::new();
         sql_options.verify_plan(&plan)?;
 
-        let df = self.session.execute_logical_plan(plan).await?;
+        let plan = match &query.params {
+            Some(params) => plan.with_param_values(params.to_param_values()?)?,
+            None => plan,
+        };
 
-        let batches = df.collect().await?;
-        let batches: Vec<_> = batches.iter().collect();
+        let df = session.execute_logical_plan(plan).await?;
 
-        let list = arrow::json::writer::record_batches_to_json_rows(&batches[..])?;
+        Ok(df)
+    }
+}
 
-        Ok(list)
+fn modified(path: &std::path::Path) -> Option<SystemTime> {
+    path.metadata()
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+#[cfg(test)]
+mod test_functions {
+    use datafusion::arrow::{
+        array::AsArray,
+        datatypes::{DataType, Int64Type},
+    };
+
+    use crate::{
+        query::functions::{FunctionLanguage, FunctionParam},
+        state::events::function,
+    };
+
+    use super::*;
+
+    fn app() -> App {
+        let dir = std::env::temp_dir().join(format!("file-fusion-{}", uuid::Uuid::new_v4()));
+        App::new(Config {
+            events_file: dir.join("events.json"),
+            settings_file: dir.join("settings.json"),
+            runtime_file: dir.join("runtime.json"),
+            functions_dir: dir.join("functions"),
+            log_filter: None,
+        })
+        .unwrap()
+    }
+
+    fn create(id: usize, name: &str, body: &str) -> Events {
+        Events::CreateFunction(function::Create {
+            id,
+            definition: FunctionDefinition {
+                name: String::from(name),
+                params: vec![FunctionParam {
+                    name: String::from("x"),
+                    data_type: DataType::Int64,
+                }],
+                return_type: DataType::Int64,
+                body: String::from(body),
+                language: FunctionLanguage::Sql,
+            },
+        })
+    }
+
+    async fn call(app: &App, sql: &str) -> i64 {
+        let batches = app
+            .session()
+            .await
+            .sql(sql)
+            .await
+            .unwrap()
+            .collect()
+            .await
+            .unwrap();
+        batches[0].column(0).as_primitive::<Int64Type>().value(0)
+    }
+
+    #[tokio::test]
+    async fn recompiles_dependents_of_replaced_function() {
+        let app = app();
+        app.update(&create(1, "inc", "x + 1")).await.unwrap();
+        app.update(&create(2, "inc_twice", "inc(inc(x))"))
+            .await
+            .unwrap();
+        assert_eq!(call(&app, "SELECT inc_twice(CAST(1 AS BIGINT))").await, 3);
+
+        app.update(&create(3, "inc", "x + 10")).await.unwrap();
+        assert_eq!(call(&app, "SELECT inc(CAST(1 AS BIGINT))").await, 11);
+        assert_eq!(call(&app, "SELECT inc_twice(CAST(1 AS BIGINT))").await, 21);
+        assert_eq!(app.list_functions().await.len(), 2);
     }
 }
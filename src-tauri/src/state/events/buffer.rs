@@ -1,7 +1,48 @@
-use crate::messages::buffer::BufferMetadata;
+use std::sync::Arc;
+
+use datafusion::arrow::datatypes::Schema;
+
+use crate::{
+    messages::buffer::BufferMetadata, profile::BufferProfile, query::schemas::SchemaOverride,
+};
 
 #[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
 pub struct Create {
     pub id: usize,
     pub metadata: BufferMetadata,
 }
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct UpdateSchema {
+    pub id: usize,
+    pub buffer: usize,
+    pub schema_override: SchemaOverride,
+}
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct PinVersions {
+    pub id: usize,
+    pub buffer: usize,
+    pub as_of: Option<i64>,
+}
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct CacheSchema {
+    pub id: usize,
+    pub buffer: usize,
+    pub fingerprint: String,
+    pub schema: Arc<Schema>,
+}
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct CreateView {
+    pub id: usize,
+    pub name: String,
+    pub statement: String,
+}
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct SaveProfile {
+    pub id: usize,
+    pub profile: BufferProfile,
+}
@@ -0,0 +1,7 @@
+use crate::query::functions::FunctionDefinition;
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct Create {
+    pub id: usize,
+    pub definition: FunctionDefinition,
+}
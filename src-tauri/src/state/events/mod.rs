@@ -1,8 +1,15 @@
 pub mod buffer;
+pub mod function;
 pub mod store;
 
 #[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
 pub enum Events {
     CreateObjectStore(store::Create),
     CreateBuffer(buffer::Create),
+    CreateView(buffer::CreateView),
+    UpdateBufferSchema(buffer::UpdateSchema),
+    PinBufferVersions(buffer::PinVersions),
+    CacheBufferSchema(buffer::CacheSchema),
+    SaveBufferProfile(buffer::SaveProfile),
+    CreateFunction(function::Create),
 }
@@ -0,0 +1,229 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, Mutex, MutexGuard},
+    time::{Duration, Instant},
+};
+
+use datafusion::execution::cache::CacheAccessor;
+use object_store::{path::Path, ObjectMeta};
+
+type Listing = Arc<Vec<ObjectMeta>>;
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// a prefix listed for the tables of a buffer
+#[derive(Debug, Clone)]
+struct Claim {
+    /// the url of the store the prefix is listed in
+    store: String,
+    ttl: Duration,
+}
+
+/// the file listings datafusion caches for listing tables, keyed by the listed prefix. a listing
+/// expires after the shortest ttl of the buffers the prefix belongs to or the default ttl.
+/// datafusion keys the listings by the prefix alone, so a prefix listed in more than one store,
+/// e.g. the same prefix in two buckets or a pinned and a live buffer, is not cached
+#[derive(Debug)]
+pub struct ListingCache {
+    default_ttl: Mutex<Duration>,
+    claims: Mutex<HashMap<Path, HashMap<usize, Claim>>>,
+    entries: Mutex<HashMap<Path, (Instant, Listing)>>,
+}
+
+impl ListingCache {
+    pub fn new(default_ttl: Duration) -> Self {
+        Self {
+            default_ttl: Mutex::new(default_ttl),
+            claims: Mutex::new(HashMap::new()),
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn set_default_ttl(&self, ttl: Duration) {
+        *lock(&self.default_ttl) = ttl;
+    }
+
+    /// records that a buffer lists a prefix in a store and drops the cached listing of it
+    pub fn claim(&self, buffer: usize, store: &str, prefix: Path, ttl: Duration) {
+        let claim = Claim {
+            store: store.to_string(),
+            ttl,
+        };
+        lock(&self.claims)
+            .entry(prefix.clone())
+            .or_default()
+            .insert(buffer, claim);
+        self.invalidate(&prefix);
+    }
+
+    /// drops the prefixes of a buffer, e.g. when its tables are deregistered
+    pub fn release(&self, buffer: usize) {
+        let mut claims = lock(&self.claims);
+        for (prefix, buffers) in claims.iter_mut() {
+            if buffers.remove(&buffer).is_some() {
+                self.invalidate(prefix);
+            }
+        }
+        claims.retain(|_, buffers| !buffers.is_empty());
+    }
+
+    /// drops the listing of a prefix so it is listed again on the next scan
+    pub fn invalidate(&self, prefix: &Path) {
+        lock(&self.entries).remove(prefix);
+    }
+
+    /// how long the listing of a prefix is cached, `None` when it is listed in more than one store
+    fn ttl(&self, prefix: &Path) -> Option<Duration> {
+        let claims = lock(&self.claims);
+        let buffers = match claims.get(prefix) {
+            Some(buffers) => buffers,
+            None => return Some(*lock(&self.default_ttl)),
+        };
+
+        let stores: HashSet<&str> = buffers.values().map(|claim| claim.store.as_str()).collect();
+        if stores.len() > 1 {
+            return None;
+        }
+
+        buffers.values().map(|claim| claim.ttl).min()
+    }
+}
+
+impl CacheAccessor<Path, Listing> for ListingCache {
+    type Extra = ObjectMeta;
+
+    fn get(&self, k: &Path) -> Option<Listing> {
+        let ttl = self.ttl(k)?;
+        let mut entries = lock(&self.entries);
+
+        match entries.get(k) {
+            Some((listed_at, listing)) if listed_at.elapsed() < ttl => Some(listing.clone()),
+            Some(_) => {
+                entries.remove(k);
+                None
+            }
+            None => None,
+        }
+    }
+
+    fn get_with_extra(&self, k: &Path, _e: &Self::Extra) -> Option<Listing> {
+        self.get(k)
+    }
+
+    fn put(&self, key: &Path, value: Listing) -> Option<Listing> {
+        self.ttl(key)?;
+        lock(&self.entries)
+            .insert(key.clone(), (Instant::now(), value))
+            .map(|(_, listing)| listing)
+    }
+
+    fn put_with_extra(&self, key: &Path, value: Listing, _e: &Self::Extra) -> Option<Listing> {
+        self.put(key, value)
+    }
+
+    fn remove(&mut self, k: &Path) -> Option<Listing> {
+        lock(&self.entries).remove(k).map(|(_, listing)| listing)
+    }
+
+    fn contains_key(&self, k: &Path) -> bool {
+        self.get(k).is_some()
+    }
+
+    fn len(&self) -> usize {
+        lock(&self.entries).len()
+    }
+
+    fn clear(&self) {
+        lock(&self.entries).clear();
+    }
+
+    fn name(&self) -> String {
+        String::from("ListingCache")
+    }
+}
+
+#[cfg(test)]
+mod test_listing_cache {
+    use crate::state::store::{
+        Connection, LocalConnection, Metadata, ObjectStore, ObjectStoreKind,
+    };
+
+    use super::*;
+
+    #[test]
+    fn expires_per_prefix() {
+        let cache = ListingCache::new(Duration::from_secs(60));
+        let short = Path::from("short");
+        let long = Path::from("long");
+        cache.claim(1, "s3://bucket/", short.clone(), Duration::ZERO);
+
+        cache.put(&short, Arc::new(Vec::new()));
+        cache.put(&long, Arc::new(Vec::new()));
+
+        assert!(cache.get(&short).is_none());
+        assert!(cache.get(&long).is_some());
+
+        cache.invalidate(&long);
+        assert!(cache.get(&long).is_none());
+    }
+
+    #[test]
+    fn uses_shortest_ttl_of_buffers() {
+        let cache = ListingCache::new(Duration::from_secs(60));
+        let prefix = Path::from("events");
+        cache.claim(1, "s3://bucket/", prefix.clone(), Duration::from_secs(60));
+        cache.claim(2, "s3://bucket/", prefix.clone(), Duration::ZERO);
+
+        cache.put(&prefix, Arc::new(Vec::new()));
+        assert!(cache.get(&prefix).is_none());
+
+        cache.release(2);
+        cache.put(&prefix, Arc::new(Vec::new()));
+        assert!(cache.get(&prefix).is_some());
+    }
+
+    #[test]
+    fn skips_prefix_of_pinned_and_live_buffers() {
+        let store = ObjectStore::new(
+            Metadata {
+                id: 0,
+                name: String::from("Local"),
+                prefix: String::new(),
+                kind: ObjectStoreKind::Local,
+            },
+            Connection::Local(LocalConnection {}),
+        )
+        .unwrap();
+        let pinned = ObjectStore {
+            versions_url: Some(String::from("versions://store-0-buffer-2-100")),
+            ..store.clone()
+        };
+        assert_ne!(store.url(), pinned.url());
+
+        let cache = ListingCache::new(Duration::from_secs(60));
+        let prefix = Path::from("events");
+        cache.claim(1, &store.url(), prefix.clone(), Duration::from_secs(60));
+        cache.claim(2, &pinned.url(), prefix.clone(), Duration::from_secs(60));
+
+        cache.put(&prefix, Arc::new(Vec::new()));
+        assert!(cache.get(&prefix).is_none());
+    }
+
+    #[test]
+    fn skips_prefix_listed_in_two_stores() {
+        let cache = ListingCache::new(Duration::from_secs(60));
+        let prefix = Path::from("events");
+        cache.claim(1, "s3://bucket-a/", prefix.clone(), Duration::from_secs(60));
+        cache.claim(2, "s3://bucket-b/", prefix.clone(), Duration::from_secs(60));
+
+        assert!(cache.put(&prefix, Arc::new(Vec::new())).is_none());
+        assert!(cache.get(&prefix).is_none());
+        assert_eq!(cache.len(), 0);
+
+        cache.release(1);
+        cache.put(&prefix, Arc::new(Vec::new()));
+        assert!(cache.get(&prefix).is_some());
+    }
+}
@@ -3,6 +3,10 @@ mod mutex_map;
 
 pub use app::*;
 pub mod events;
+pub mod listing_cache;
 pub use mutex_map::{Id, MutexMap};
+pub mod runtime;
+pub mod settings;
 pub mod store;
 pub mod table;
+pub mod versions;
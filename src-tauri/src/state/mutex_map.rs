@@ -101,6 +101,19 @@ impl<T: Clone> MutexMap<T> {
         items
     }
 
+    pub async fn entries(&self) -> Vec<(usize, T)> {
+        let entries = {
+            let lock = self.get_lock().await;
+            let entries: Vec<(usize, T)> = lock
+                .iter()
+                .map(|(key, item)| (*key, item.clone()))
+                .collect();
+            entries
+        };
+
+        entries
+    }
+
     pub async fn insert(&self, id: usize, item: T) -> Option<T> {
         let result = {
             let mut lock = self.get_lock().await;
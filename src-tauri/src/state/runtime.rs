@@ -0,0 +1,103 @@
+use std::{fs::create_dir_all, path::PathBuf, sync::Arc};
+
+use datafusion::execution::{
+    cache::cache_manager::CacheManagerConfig,
+    context::{SessionConfig, SessionContext},
+    disk_manager::DiskManagerConfig,
+    memory_pool::{FairSpillPool, GreedyMemoryPool, MemoryPool},
+    runtime_env::{RuntimeConfig, RuntimeEnv},
+};
+
+use crate::errors::Error;
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MemoryPoolKind {
+    /// every spilling operator gets an equal share of the pool
+    Fair,
+    /// memory is handed out first come first served
+    Greedy,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct RuntimeSettings {
+    /// the memory available to queries in bytes, unbounded when not set
+    pub memory_limit: Option<usize>,
+    pub memory_pool: MemoryPoolKind,
+    /// where sorts and aggregations spill to, the os temp dir when not set
+    pub spill_dir: Option<PathBuf>,
+    /// the number of cpus when not set
+    pub target_partitions: Option<usize>,
+    pub batch_size: usize,
+}
+
+impl Default for RuntimeSettings {
+    fn default() -> Self {
+        Self {
+            memory_limit: None,
+            memory_pool: MemoryPoolKind::Fair,
+            spill_dir: None,
+            target_partitions: None,
+            batch_size: 8192,
+        }
+    }
+}
+
+impl RuntimeSettings {
+    pub fn validate(&self) -> Result<(), Error> {
+        if self.memory_limit == Some(0) {
+            return Err(Error::InvalidSettings(String::from(
+                "memory limit must be greater than 0",
+            )));
+        }
+        if self.target_partitions == Some(0) {
+            return Err(Error::InvalidSettings(String::from(
+                "target partitions must be greater than 0",
+            )));
+        }
+        if self.batch_size == 0 {
+            return Err(Error::InvalidSettings(String::from(
+                "batch size must be greater than 0",
+            )));
+        }
+        if let Some(dir) = &self.spill_dir {
+            if dir.exists() && !dir.is_dir() {
+                return Err(Error::InvalidSettings(format!(
+                    "spill dir {} is not a directory",
+                    dir.display()
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn build_session(&self, cache_config: CacheManagerConfig) -> Result<SessionContext, Error> {
+        let mut runtime_config = RuntimeConfig::new().with_cache_manager(cache_config);
+
+        if let Some(limit) = self.memory_limit {
+            let pool: Arc<dyn MemoryPool> = match self.memory_pool {
+                MemoryPoolKind::Fair => Arc::new(FairSpillPool::new(limit)),
+                MemoryPoolKind::Greedy => Arc::new(GreedyMemoryPool::new(limit)),
+            };
+            runtime_config = runtime_config.with_memory_pool(pool);
+        }
+
+        let disk_manager = match &self.spill_dir {
+            Some(dir) => {
+                create_dir_all(dir)?;
+                DiskManagerConfig::NewSpecified(vec![dir.clone()])
+            }
+            None => DiskManagerConfig::NewOs,
+        };
+        runtime_config = runtime_config.with_disk_manager(disk_manager);
+
+        let mut session_config = SessionConfig::new().with_batch_size(self.batch_size);
+        if let Some(partitions) = self.target_partitions {
+            session_config = session_config.with_target_partitions(partitions);
+        }
+
+        let runtime = Arc::new(RuntimeEnv::new(runtime_config)?);
+        Ok(SessionContext::new_with_config_rt(session_config, runtime))
+    }
+}
@@ -0,0 +1,199 @@
+use std::{
+    fs::{create_dir_all, read_to_string, rename, write},
+    path::Path,
+    time::Duration,
+};
+
+use tracing::{debug, info};
+use tracing_subscriber::EnvFilter;
+
+use crate::errors::Error;
+
+use super::runtime::RuntimeSettings;
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(default)]
+pub struct QuerySettings {
+    /// the number of rows returned when a query does not set a limit, unlimited when not set
+    pub row_limit: Option<usize>,
+    /// queries are cancelled after this many seconds, no timeout when not set
+    pub timeout_secs: Option<u64>,
+}
+
+impl QuerySettings {
+    pub fn timeout(&self) -> Option<Duration> {
+        self.timeout_secs.map(Duration::from_secs)
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct CacheSettings {
+    /// how long file listings and registered buffer tables are reused before the files are
+    /// listed again, unless the buffer sets its own ttl
+    pub listing_ttl_secs: u64,
+    /// the number of paginated listings kept in memory for continuation tokens
+    pub max_listings: usize,
+}
+
+impl Default for CacheSettings {
+    fn default() -> Self {
+        Self {
+            listing_ttl_secs: 5 * 60,
+            max_listings: 16,
+        }
+    }
+}
+
+impl CacheSettings {
+    pub fn listing_ttl(&self) -> Duration {
+        Duration::from_secs(self.listing_ttl_secs)
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct Settings {
+    /// an env filter directive, e.g. `info` or `app=debug,datafusion=warn`
+    pub log_level: String,
+    /// only used in debug builds
+    pub open_devtools: bool,
+    pub query: QuerySettings,
+    pub cache: CacheSettings,
+    pub runtime: RuntimeSettings,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            log_level: String::from("info"),
+            open_devtools: true,
+            query: QuerySettings::default(),
+            cache: CacheSettings::default(),
+            runtime: RuntimeSettings::default(),
+        }
+    }
+}
+
+impl Settings {
+    /// reads the settings file, missing files and fields use the defaults
+    pub fn load(path: &Path) -> Result<Self, Error> {
+        if !path.exists() {
+            debug!(path = ?path, "settings file not found, using defaults");
+            return Ok(Self::default());
+        }
+
+        let settings: Self = serde_json::from_str(&read_to_string(path)?)?;
+        settings.validate()?;
+
+        Ok(settings)
+    }
+
+    /// moves the runtime settings of a runtime file written by earlier versions into the settings
+    /// file, the runtime file is renamed so it is only migrated once
+    pub fn migrate_runtime_file(path: &Path, runtime_file: &Path) -> Result<(), Error> {
+        if !runtime_file.exists() {
+            return Ok(());
+        }
+
+        let runtime: RuntimeSettings = serde_json::from_str(&read_to_string(runtime_file)?)?;
+        runtime.validate()?;
+
+        let mut settings = Self::load(path)?;
+        settings.runtime = runtime;
+        settings.save(path)?;
+
+        rename(runtime_file, runtime_file.with_extension("json.migrated"))?;
+        info!(runtime_file = ?runtime_file, "migrated runtime settings into the settings file");
+
+        Ok(())
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), Error> {
+        if let Some(dir) = path.parent() {
+            create_dir_all(dir)?;
+        }
+
+        write(path, serde_json::to_string_pretty(self)?)?;
+        info!(path = ?path, "saved settings");
+
+        Ok(())
+    }
+
+    pub fn validate(&self) -> Result<(), Error> {
+        if EnvFilter::try_new(&self.log_level).is_err() {
+            return Err(Error::InvalidSettings(format!(
+                "invalid log level `{}`",
+                self.log_level
+            )));
+        }
+        if self.query.row_limit == Some(0) {
+            return Err(Error::InvalidSettings(String::from(
+                "row limit must be greater than 0",
+            )));
+        }
+        if self.query.timeout_secs == Some(0) {
+            return Err(Error::InvalidSettings(String::from(
+                "query timeout must be greater than 0",
+            )));
+        }
+        if self.cache.max_listings == 0 {
+            return Err(Error::InvalidSettings(String::from(
+                "max listings must be greater than 0",
+            )));
+        }
+
+        self.runtime.validate()
+    }
+}
+
+#[cfg(test)]
+mod test_settings {
+    use super::*;
+
+    #[test]
+    fn fills_missing_fields_with_defaults() {
+        let settings: Settings =
+            serde_json::from_str(r#"{"log_level": "debug", "query": {"timeout_secs": 30}}"#)
+                .unwrap();
+
+        assert_eq!(settings.log_level, "debug");
+        assert_eq!(settings.query.row_limit, None);
+        assert_eq!(settings.query.timeout(), Some(Duration::from_secs(30)));
+        assert_eq!(settings.cache, CacheSettings::default());
+        assert_eq!(settings.runtime, RuntimeSettings::default());
+    }
+
+    #[test]
+    fn rejects_invalid_settings() {
+        let mut settings = Settings::default();
+        assert!(settings.validate().is_ok());
+
+        settings.cache.max_listings = 0;
+        assert!(settings.validate().is_err());
+
+        let mut settings = Settings::default();
+        settings.runtime.batch_size = 0;
+        assert!(settings.validate().is_err());
+    }
+
+    #[test]
+    fn migrates_runtime_file() {
+        let dir = std::env::temp_dir().join(format!("file-fusion-{}", uuid::Uuid::new_v4()));
+        create_dir_all(&dir).unwrap();
+        let path = dir.join("settings.json");
+        let runtime_file = dir.join("runtime.json");
+        write(&path, r#"{"log_level": "debug"}"#).unwrap();
+        write(&runtime_file, r#"{"batch_size": 1024}"#).unwrap();
+
+        Settings::migrate_runtime_file(&path, &runtime_file).unwrap();
+        let settings = Settings::load(&path).unwrap();
+
+        assert_eq!(settings.log_level, "debug");
+        assert_eq!(settings.runtime.batch_size, 1024);
+        assert!(!runtime_file.exists());
+        assert!(dir.join("runtime.json.migrated").exists());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
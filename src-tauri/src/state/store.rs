@@ -1,11 +1,17 @@
-use crate::errors::Error;
-use datafusion::execution::context::SessionContext;
+use crate::{errors::Error, query::naming::store_schema};
+
+use super::versions::{list_versions, PinnedClient};
+use aws_sdk_s3::{
+    config::{BehaviorVersion, Credentials, Region},
+    Client,
+};
+use datafusion::{catalog::schema::MemorySchemaProvider, execution::context::SessionContext};
 use directories::UserDirs;
 use object_store::{
-    aws::AmazonS3Builder, local::LocalFileSystem, ObjectStore as ObjectStoreClient,
+    aws::AmazonS3Builder, local::LocalFileSystem, path::Path, ObjectStore as ObjectStoreClient,
 };
 use std::{cmp::Ordering, sync::Arc};
-use tracing::{debug, info};
+use tracing::{debug, info, warn};
 use url::Url;
 
 #[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq)]
@@ -63,6 +69,30 @@ pub struct RemoteConnection {
     pub endpoint: String,
 }
 
+impl RemoteConnection {
+    /// a client for the s3 apis object_store does not cover, e.g. object versions
+    pub fn s3_client(&self) -> Client {
+        let credentials = Credentials::new(
+            &self.access_key,
+            &self.access_key_secret,
+            None,
+            None,
+            "file-fusion",
+        );
+
+        let mut config = aws_sdk_s3::Config::builder()
+            .behavior_version(BehaviorVersion::latest())
+            .region(Region::new(self.region.clone()))
+            .credentials_provider(credentials)
+            .force_path_style(true);
+        if !self.endpoint.is_empty() {
+            config = config.endpoint_url(&self.endpoint);
+        }
+
+        Client::from_conf(config.build())
+    }
+}
+
 #[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
 pub enum Connection {
     Local(LocalConnection),
@@ -75,11 +105,13 @@ pub struct ObjectStore {
     pub metadata: Metadata,
     pub connection: Connection,
     pub client: Arc<dyn ObjectStoreClient>,
+    /// the url datafusion reads the client through when it only sees pinned object versions
+    pub versions_url: Option<String>,
 }
 
-impl ObjectStore {
-    pub fn new(metadata: Metadata, connection: Connection) -> Result<Self, Error> {
-        let client: Arc<dyn ObjectStoreClient> = match connection.clone() {
+impl Connection {
+    pub fn build_client(&self) -> Result<Arc<dyn ObjectStoreClient>, Error> {
+        let client: Arc<dyn ObjectStoreClient> = match self.clone() {
             Connection::Local(_) => Arc::new(LocalFileSystem::new()),
             Connection::Remote(connection) => {
                 let s3 = AmazonS3Builder::new()
@@ -94,14 +126,255 @@ impl ObjectStore {
             }
         };
 
+        Ok(client)
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionFailureKind {
+    Configuration,
+    Dns,
+    Tls,
+    Auth,
+    Permission,
+    BucketNotFound,
+    Unknown,
+}
+
+/// the errors below an error, starting with the error itself
+fn error_chain<'a>(
+    error: &'a (dyn std::error::Error + 'static),
+) -> impl Iterator<Item = &'a (dyn std::error::Error + 'static)> {
+    std::iter::successors(Some(error), |error| error.source())
+}
+
+/// the code of an s3 error response, e.g. `AccessDenied` in `<Code>AccessDenied</Code>`
+fn s3_error_code(message: &str) -> Option<&str> {
+    let start = message.find("<Code>")? + "<Code>".len();
+    let end = message[start..].find("</Code>")? + start;
+
+    Some(&message[start..end])
+}
+
+/// the http status object store includes in its request errors, e.g. `status 403 Forbidden`
+fn http_status(message: &str) -> Option<u16> {
+    let start = message.find("with status ")? + "with status ".len();
+
+    message.get(start..start + 3)?.parse().ok()
+}
+
+impl ConnectionFailureKind {
+    fn from_s3_code(code: &str) -> Option<Self> {
+        match code {
+            "NoSuchBucket" => Some(Self::BucketNotFound),
+            "InvalidAccessKeyId" | "SignatureDoesNotMatch" | "ExpiredToken" | "InvalidToken" => {
+                Some(Self::Auth)
+            }
+            "AccessDenied" | "AllAccessDisabled" => Some(Self::Permission),
+            _ => None,
+        }
+    }
+
+    fn from_status(status: u16) -> Option<Self> {
+        match status {
+            401 => Some(Self::Auth),
+            403 => Some(Self::Permission),
+            404 => Some(Self::BucketNotFound),
+            _ => None,
+        }
+    }
+
+    /// connection errors below the http client error, they do not contain the request url
+    fn from_connect_error(error: &reqwest::Error) -> Option<Self> {
+        let source = std::error::Error::source(error)?;
+        error_chain(source).find_map(|error| {
+            let message = error.to_string().to_lowercase();
+            if message.contains("dns error") || message.contains("failed to lookup address") {
+                Some(Self::Dns)
+            } else if message.contains("certificate") || message.contains("handshake") {
+                Some(Self::Tls)
+            } else {
+                None
+            }
+        })
+    }
+
+    /// classifies by the error variant, the s3 error code or http status of the response and the
+    /// connection error of the http client. messages are only searched for these structured
+    /// parts, so paths and bucket names do not affect the category
+    fn from_error(error: &object_store::Error) -> Self {
+        match error {
+            object_store::Error::UnknownConfigurationKey { .. }
+            | object_store::Error::InvalidPath { .. } => return Self::Configuration,
+            object_store::Error::NotFound { .. } => return Self::BucketNotFound,
+            _ => {}
+        }
+
+        for error in error_chain(error) {
+            if let Some(error) = error.downcast_ref::<reqwest::Error>() {
+                if let Some(kind) = error
+                    .status()
+                    .and_then(|status| Self::from_status(status.as_u16()))
+                {
+                    return kind;
+                }
+                if let Some(kind) = Self::from_connect_error(error) {
+                    return kind;
+                }
+                continue;
+            }
+
+            let message = error.to_string();
+            if let Some(kind) = s3_error_code(&message).and_then(Self::from_s3_code) {
+                return kind;
+            }
+            if let Some(kind) = http_status(&message).and_then(Self::from_status) {
+                return kind;
+            }
+        }
+
+        Self::Unknown
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub enum ConnectionTest {
+    Success,
+    Failure {
+        kind: ConnectionFailureKind,
+        message: String,
+    },
+}
+
+impl ConnectionTest {
+    #[tracing::instrument(name = "testing connection", skip(connection))]
+    pub async fn run(connection: &Connection, prefix: &str) -> Self {
+        let client = match connection.build_client() {
+            Ok(client) => client,
+            Err(e) => {
+                return Self::Failure {
+                    kind: ConnectionFailureKind::Configuration,
+                    message: e.to_string(),
+                }
+            }
+        };
+
+        let path = match Path::parse(prefix) {
+            Ok(path) => path,
+            Err(e) => {
+                return Self::Failure {
+                    kind: ConnectionFailureKind::Configuration,
+                    message: e.to_string(),
+                }
+            }
+        };
+
+        let list = client.list_with_delimiter(Some(&path)).await;
+        match list {
+            Ok(list) => {
+                debug!(
+                    num_objects = list.objects.len(),
+                    num_prefixes = list.common_prefixes.len(),
+                    "connection test succeeded"
+                );
+                Self::Success
+            }
+            Err(e) => {
+                let kind = ConnectionFailureKind::from_error(&e);
+                warn!(?e, ?kind, "connection test failed");
+                Self::Failure {
+                    kind,
+                    message: e.to_string(),
+                }
+            }
+        }
+    }
+}
+
+impl ObjectStore {
+    pub fn new(metadata: Metadata, connection: Connection) -> Result<Self, Error> {
+        let client = connection.build_client()?;
+
         Ok(Self {
             registered: false,
             metadata,
             connection,
             client,
+            versions_url: None,
+        })
+    }
+
+    /// the path datafusion uses to resolve a prefix in this store
+    pub fn table_path(&self, prefix: &str) -> String {
+        if let Some(url) = &self.versions_url {
+            return format!("{url}/{prefix}");
+        }
+
+        match &self.connection {
+            // object store paths have no leading `/`, an absolute url keeps datafusion from
+            // resolving them relative to the working directory
+            Connection::Local(_) => format!("file:///{}", prefix.trim_start_matches('/')),
+            Connection::Remote(connection) => {
+                let bucket_name = &connection.bucket;
+                format!("s3://{bucket_name}/{prefix}")
+            }
+        }
+    }
+
+    /// a copy of the store that only sees the versions of the objects below the prefixes as they
+    /// were at `as_of`, in milliseconds since the unix epoch. the copy is read through its own url
+    /// so the pin only applies to the tables of `buffer`
+    #[tracing::instrument(name = "pinning object versions", skip(self), fields(
+        object_store = self.metadata.id
+    ))]
+    pub async fn pin(&self, buffer: usize, as_of: i64, prefixes: &[Path]) -> Result<Self, Error> {
+        if self.versions_url.is_some() {
+            return Ok(self.clone());
+        }
+
+        let connection = match &self.connection {
+            Connection::Local(_) => {
+                return Err(Error::UnversionedStore(self.metadata.name.clone()))
+            }
+            Connection::Remote(connection) => connection,
+        };
+
+        let files = list_versions(connection, prefixes, as_of).await?;
+        let client = PinnedClient::new(self.client.clone(), as_of, files);
+
+        Ok(Self {
+            client: Arc::new(client),
+            versions_url: Some(format!(
+                "versions://store-{}-buffer-{}-{}",
+                self.metadata.id, buffer, as_of
+            )),
+            ..self.clone()
         })
     }
 
+    /// identifies the store to datafusion, the url its table paths start with
+    pub fn url(&self) -> String {
+        self.table_path("")
+    }
+
+    /// makes the client of a pinned store available to datafusion under its versions url
+    pub fn register_versions(&self, ctx: &SessionContext) -> Result<(), Error> {
+        if let Some(url) = &self.versions_url {
+            let url = Url::parse(url)?;
+            ctx.runtime_env()
+                .register_object_store(&url, self.client.clone());
+            debug!(object_store = self.metadata.id, url = %url, "registered pinned object store");
+        }
+
+        Ok(())
+    }
+
+    /// registers the store with a new session, e.g. after the runtime settings changed
+    pub fn reregister(&mut self, ctx: &SessionContext) -> Result<(), Error> {
+        self.registered = false;
+        self.register(ctx)
+    }
+
     pub fn register(&mut self, ctx: &SessionContext) -> Result<(), Error> {
         debug!(object_store = self.metadata.id, "registering object store");
         if self.registered {
@@ -111,6 +384,18 @@ impl ObjectStore {
             );
             return Ok(());
         }
+        let catalog = ctx.state().config_options().catalog.default_catalog.clone();
+        let catalog = ctx
+            .catalog(&catalog)
+            .ok_or(Error::NotFound(format!("catalog {}", catalog)))?;
+        let schema = store_schema(&self.metadata.name, self.metadata.id);
+        catalog.register_schema(&schema, Arc::new(MemorySchemaProvider::new()))?;
+        debug!(
+            object_store = self.metadata.id,
+            schema = %schema,
+            "registered object store schema"
+        );
+
         match &self.connection {
             Connection::Local(_) => {
                 self.registered = true;
@@ -148,3 +433,55 @@ pub fn get_home_dir() -> Result<String, Error> {
         .ok_or(Error::HomeDirNotFound)?;
     Ok(String::from(home_dir))
 }
+
+#[cfg(test)]
+mod test_connection_failure {
+    use super::*;
+
+    fn request_error(message: &str) -> object_store::Error {
+        object_store::Error::Generic {
+            store: "S3",
+            source: message.into(),
+        }
+    }
+
+    #[test]
+    fn classifies_s3_error_codes() {
+        let error = request_error(
+            "Error performing list request: Client error with status 403 Forbidden: \
+             <Error><Code>SignatureDoesNotMatch</Code></Error>",
+        );
+        assert_eq!(
+            ConnectionFailureKind::from_error(&error),
+            ConnectionFailureKind::Auth
+        );
+
+        let error = request_error(
+            "Error performing list request: Client error with status 404 Not Found: \
+             <Error><Code>NoSuchBucket</Code></Error>",
+        );
+        assert_eq!(
+            ConnectionFailureKind::from_error(&error),
+            ConnectionFailureKind::BucketNotFound
+        );
+    }
+
+    #[test]
+    fn classifies_http_status() {
+        let error =
+            request_error("Error performing list request: Client error with status 403 Forbidden");
+        assert_eq!(
+            ConnectionFailureKind::from_error(&error),
+            ConnectionFailureKind::Permission
+        );
+    }
+
+    #[test]
+    fn ignores_paths_and_bucket_names() {
+        let error = request_error("Error performing list request for tls-401-403/data: timed out");
+        assert_eq!(
+            ConnectionFailureKind::from_error(&error),
+            ConnectionFailureKind::Unknown
+        );
+    }
+}
@@ -0,0 +1,442 @@
+use std::{collections::BTreeMap, collections::HashMap, fmt, sync::Arc};
+
+use async_trait::async_trait;
+use aws_sdk_s3::{error::DisplayErrorContext, primitives::DateTime};
+use bytes::Bytes;
+use chrono::{TimeZone, Utc};
+use futures::{stream::BoxStream, StreamExt};
+use object_store::{
+    path::Path, GetOptions, GetResult, ListResult, MultipartId, ObjectMeta,
+    ObjectStore as ObjectStoreClient, PutOptions, PutResult,
+};
+use tokio::io::AsyncWrite;
+use tracing::{debug, info};
+
+use crate::errors::Error;
+
+use super::store::RemoteConnection;
+
+fn millis(date: Option<&DateTime>) -> Option<i64> {
+    date.and_then(|date| date.to_millis().ok())
+}
+
+/// the newest version and delete marker of each key that were written at or before `as_of`
+#[derive(Default)]
+struct VersionsAsOf {
+    as_of: i64,
+    /// the time a version was written, whether it is the current version and the version
+    versions: HashMap<String, (i64, bool, Option<ObjectMeta>)>,
+    delete_markers: HashMap<String, i64>,
+}
+
+impl VersionsAsOf {
+    /// the versions of a key are listed newest first, also across pages, so the first version at
+    /// or before `as_of` is kept even when older versions were written in the same millisecond
+    fn insert_version(
+        &mut self,
+        key: &str,
+        modified: i64,
+        is_latest: bool,
+        meta: Option<ObjectMeta>,
+    ) {
+        if modified <= self.as_of {
+            self.versions
+                .entry(key.to_string())
+                .or_insert((modified, is_latest, meta));
+        }
+    }
+
+    fn insert_delete_marker(&mut self, key: &str, modified: i64) {
+        if modified <= self.as_of {
+            self.delete_markers
+                .entry(key.to_string())
+                .or_insert(modified);
+        }
+    }
+
+    /// versions and delete markers are listed separately, a delete marker hides the version when
+    /// it was written after it. at the same millisecond the current version is newer than any
+    /// delete marker, otherwise the object is treated as deleted
+    fn into_files(self) -> Vec<ObjectMeta> {
+        let delete_markers = self.delete_markers;
+
+        self.versions
+            .into_iter()
+            .filter_map(|(key, (modified, is_latest, meta))| {
+                let is_deleted = delete_markers.get(&key).map_or(false, |deleted| {
+                    *deleted > modified || (*deleted == modified && !is_latest)
+                });
+                if is_deleted {
+                    None
+                } else {
+                    meta
+                }
+            })
+            .collect()
+    }
+}
+
+/// lists the version of every object below the prefixes as it was at `as_of`, in milliseconds
+/// since the unix epoch, objects created after or deleted before `as_of` are left out
+#[tracing::instrument(name = "listing object versions", skip(connection))]
+pub async fn list_versions(
+    connection: &RemoteConnection,
+    prefixes: &[Path],
+    as_of: i64,
+) -> Result<Vec<ObjectMeta>, Error> {
+    let client = connection.s3_client();
+    let mut versions = VersionsAsOf {
+        as_of,
+        ..Default::default()
+    };
+
+    for prefix in prefixes {
+        let mut request = client.list_object_versions().bucket(&connection.bucket);
+        if !prefix.as_ref().is_empty() {
+            request = request.prefix(prefix.as_ref());
+        }
+
+        let mut pages = request.into_paginator().send();
+        while let Some(page) = pages.next().await {
+            let page =
+                page.map_err(|e| Error::ObjectVersions(DisplayErrorContext(e).to_string()))?;
+
+            for version in page.versions() {
+                let (key, modified) = match (version.key(), millis(version.last_modified())) {
+                    (Some(key), Some(modified)) => (key, modified),
+                    _ => continue,
+                };
+                let location = match Path::parse(key) {
+                    Ok(location) if location.prefix_matches(prefix) => location,
+                    _ => continue,
+                };
+
+                let meta = Utc
+                    .timestamp_millis_opt(modified)
+                    .single()
+                    .map(|last_modified| ObjectMeta {
+                        location,
+                        last_modified,
+                        size: version.size().unwrap_or_default() as usize,
+                        e_tag: version.e_tag().map(String::from),
+                        version: version.version_id().map(String::from),
+                    });
+                versions.insert_version(
+                    key,
+                    modified,
+                    version.is_latest().unwrap_or_default(),
+                    meta,
+                );
+            }
+
+            for marker in page.delete_markers() {
+                if let (Some(key), Some(modified)) = (marker.key(), millis(marker.last_modified()))
+                {
+                    versions.insert_delete_marker(key, modified);
+                }
+            }
+        }
+    }
+
+    let files = versions.into_files();
+    info!(num_files = files.len(), "resolved object versions");
+
+    Ok(files)
+}
+
+/// a read only client that only sees the given object versions, reads of a pinned object ask
+/// the inner client for its version
+#[derive(Debug)]
+pub struct PinnedClient {
+    inner: Arc<dyn ObjectStoreClient>,
+    as_of: i64,
+    files: BTreeMap<Path, ObjectMeta>,
+}
+
+impl PinnedClient {
+    pub fn new(inner: Arc<dyn ObjectStoreClient>, as_of: i64, files: Vec<ObjectMeta>) -> Self {
+        let files = files
+            .into_iter()
+            .map(|meta| (meta.location.clone(), meta))
+            .collect();
+
+        Self {
+            inner,
+            as_of,
+            files,
+        }
+    }
+
+    fn pinned(&self, location: &Path) -> object_store::Result<&ObjectMeta> {
+        self.files
+            .get(location)
+            .ok_or_else(|| object_store::Error::NotFound {
+                path: location.to_string(),
+                source: format!("no version of the object at {}", self.as_of).into(),
+            })
+    }
+
+    fn matching<'a>(&'a self, prefix: Option<&'a Path>) -> impl Iterator<Item = &'a ObjectMeta> {
+        self.files
+            .values()
+            .filter(move |meta| prefix.map_or(true, |prefix| meta.location.prefix_matches(prefix)))
+    }
+}
+
+impl fmt::Display for PinnedClient {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "PinnedClient({}, as of {})", self.inner, self.as_of)
+    }
+}
+
+#[async_trait]
+impl ObjectStoreClient for PinnedClient {
+    async fn put_opts(
+        &self,
+        _location: &Path,
+        _bytes: Bytes,
+        _opts: PutOptions,
+    ) -> object_store::Result<PutResult> {
+        Err(object_store::Error::NotImplemented)
+    }
+
+    async fn put_multipart(
+        &self,
+        _location: &Path,
+    ) -> object_store::Result<(MultipartId, Box<dyn AsyncWrite + Unpin + Send>)> {
+        Err(object_store::Error::NotImplemented)
+    }
+
+    async fn abort_multipart(
+        &self,
+        _location: &Path,
+        _multipart_id: &MultipartId,
+    ) -> object_store::Result<()> {
+        Err(object_store::Error::NotImplemented)
+    }
+
+    async fn get_opts(
+        &self,
+        location: &Path,
+        options: GetOptions,
+    ) -> object_store::Result<GetResult> {
+        let meta = self.pinned(location)?;
+        debug!(location = %location, version = ?meta.version, "getting pinned object version");
+
+        let options = GetOptions {
+            version: meta.version.clone(),
+            ..options
+        };
+        self.inner.get_opts(location, options).await
+    }
+
+    async fn head(&self, location: &Path) -> object_store::Result<ObjectMeta> {
+        self.pinned(location).cloned()
+    }
+
+    async fn delete(&self, _location: &Path) -> object_store::Result<()> {
+        Err(object_store::Error::NotImplemented)
+    }
+
+    fn list(&self, prefix: Option<&Path>) -> BoxStream<'_, object_store::Result<ObjectMeta>> {
+        let files: Vec<_> = self.matching(prefix).cloned().map(Ok).collect();
+
+        futures::stream::iter(files).boxed()
+    }
+
+    async fn list_with_delimiter(&self, prefix: Option<&Path>) -> object_store::Result<ListResult> {
+        let base = prefix.cloned().unwrap_or_default();
+        let mut common_prefixes = Vec::new();
+        let mut objects = Vec::new();
+
+        for meta in self.matching(prefix) {
+            let mut parts = match meta.location.prefix_match(&base) {
+                Some(parts) => parts,
+                None => continue,
+            };
+
+            match (parts.next(), parts.next()) {
+                (Some(_), None) => objects.push(meta.clone()),
+                (Some(part), Some(_)) => {
+                    let common_prefix = base.child(part);
+                    if !common_prefixes.contains(&common_prefix) {
+                        common_prefixes.push(common_prefix);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        Ok(ListResult {
+            common_prefixes,
+            objects,
+        })
+    }
+
+    async fn copy(&self, _from: &Path, _to: &Path) -> object_store::Result<()> {
+        Err(object_store::Error::NotImplemented)
+    }
+
+    async fn copy_if_not_exists(&self, _from: &Path, _to: &Path) -> object_store::Result<()> {
+        Err(object_store::Error::NotImplemented)
+    }
+}
+
+#[cfg(test)]
+mod test_versions {
+    use std::sync::Mutex;
+
+    use object_store::memory::InMemory;
+
+    use super::*;
+
+    /// an in memory store that records the version each object is read at
+    #[derive(Debug, Default)]
+    struct RecordingClient {
+        inner: InMemory,
+        versions: Mutex<Vec<Option<String>>>,
+    }
+
+    impl fmt::Display for RecordingClient {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "RecordingClient")
+        }
+    }
+
+    #[async_trait]
+    impl ObjectStoreClient for RecordingClient {
+        async fn put_opts(
+            &self,
+            location: &Path,
+            bytes: Bytes,
+            opts: PutOptions,
+        ) -> object_store::Result<PutResult> {
+            self.inner.put_opts(location, bytes, opts).await
+        }
+
+        async fn put_multipart(
+            &self,
+            location: &Path,
+        ) -> object_store::Result<(MultipartId, Box<dyn AsyncWrite + Unpin + Send>)> {
+            self.inner.put_multipart(location).await
+        }
+
+        async fn abort_multipart(
+            &self,
+            location: &Path,
+            multipart_id: &MultipartId,
+        ) -> object_store::Result<()> {
+            self.inner.abort_multipart(location, multipart_id).await
+        }
+
+        async fn get_opts(
+            &self,
+            location: &Path,
+            options: GetOptions,
+        ) -> object_store::Result<GetResult> {
+            self.versions.lock().unwrap().push(options.version.clone());
+            // objects in memory are not versioned
+            let options = GetOptions {
+                version: None,
+                ..options
+            };
+            self.inner.get_opts(location, options).await
+        }
+
+        async fn delete(&self, location: &Path) -> object_store::Result<()> {
+            self.inner.delete(location).await
+        }
+
+        fn list(&self, prefix: Option<&Path>) -> BoxStream<'_, object_store::Result<ObjectMeta>> {
+            self.inner.list(prefix)
+        }
+
+        async fn list_with_delimiter(
+            &self,
+            prefix: Option<&Path>,
+        ) -> object_store::Result<ListResult> {
+            self.inner.list_with_delimiter(prefix).await
+        }
+
+        async fn copy(&self, from: &Path, to: &Path) -> object_store::Result<()> {
+            self.inner.copy(from, to).await
+        }
+
+        async fn copy_if_not_exists(&self, from: &Path, to: &Path) -> object_store::Result<()> {
+            self.inner.copy_if_not_exists(from, to).await
+        }
+    }
+
+    #[tokio::test]
+    async fn reads_pinned_versions() {
+        let inner = Arc::new(RecordingClient::default());
+        for key in ["data/a.parquet", "data/x/b.parquet", "data/c.parquet"] {
+            inner
+                .put(&Path::from(key), Bytes::from_static(b"x"))
+                .await
+                .unwrap();
+        }
+        // `data/c.parquet` was written after the pinned time
+        let files = vec![
+            meta("data/a.parquet", "1").unwrap(),
+            meta("data/x/b.parquet", "2").unwrap(),
+        ];
+        let client = PinnedClient::new(inner.clone(), 100, files);
+
+        client.get(&Path::from("data/a.parquet")).await.unwrap();
+        assert_eq!(
+            *inner.versions.lock().unwrap(),
+            vec![Some(String::from("1"))]
+        );
+        assert!(matches!(
+            client.get(&Path::from("data/c.parquet")).await,
+            Err(object_store::Error::NotFound { .. })
+        ));
+
+        let listing = client
+            .list_with_delimiter(Some(&Path::from("data")))
+            .await
+            .unwrap();
+        assert_eq!(listing.objects.len(), 1);
+        assert_eq!(listing.objects[0].location, Path::from("data/a.parquet"));
+        assert_eq!(listing.objects[0].version.as_deref(), Some("1"));
+        assert_eq!(listing.common_prefixes, vec![Path::from("data/x")]);
+    }
+
+    fn meta(key: &str, version: &str) -> Option<ObjectMeta> {
+        Some(ObjectMeta {
+            location: Path::from(key),
+            last_modified: Utc::now(),
+            size: 0,
+            e_tag: None,
+            version: Some(String::from(version)),
+        })
+    }
+
+    #[test]
+    fn keeps_latest_version_before_timestamp() {
+        let mut versions = VersionsAsOf {
+            as_of: 100,
+            ..Default::default()
+        };
+
+        // listed newest first like the api lists them
+        versions.insert_version("a.parquet", 150, true, meta("a.parquet", "4"));
+        versions.insert_version("a.parquet", 50, false, meta("a.parquet", "3"));
+        versions.insert_version("a.parquet", 50, false, meta("a.parquet", "2"));
+        versions.insert_version("a.parquet", 10, false, meta("a.parquet", "1"));
+        versions.insert_version("b.parquet", 20, false, meta("b.parquet", "1"));
+        versions.insert_delete_marker("b.parquet", 30);
+        versions.insert_version("c.parquet", 120, true, meta("c.parquet", "1"));
+        versions.insert_version("d.parquet", 40, true, meta("d.parquet", "2"));
+        versions.insert_delete_marker("d.parquet", 40);
+
+        let mut files = versions.into_files();
+        files.sort_by(|a, b| a.location.cmp(&b.location));
+
+        assert_eq!(files.len(), 2);
+        assert_eq!(files[0].location, Path::from("a.parquet"));
+        assert_eq!(files[0].version.as_deref(), Some("3"));
+        assert_eq!(files[1].location, Path::from("d.parquet"));
+    }
+}
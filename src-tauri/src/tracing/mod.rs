@@ -4,18 +4,29 @@ use tracing::{
 };
 use tracing_bunyan_formatter::{BunyanFormattingLayer, JsonStorageLayer};
 use tracing_log::LogTracer;
-use tracing_subscriber::{layer::SubscriberExt, EnvFilter, Registry};
+use tracing_subscriber::{layer::SubscriberExt, reload, EnvFilter, Registry};
 
-pub fn get_subscriber(name: String, env_filter: String) -> impl Subscriber + Send + Sync {
+use crate::errors::Error;
+
+/// changes the env filter of the subscriber at runtime
+pub type LogFilterHandle = reload::Handle<EnvFilter, Registry>;
+
+pub fn get_subscriber(
+    name: String,
+    env_filter: String,
+) -> (impl Subscriber + Send + Sync, LogFilterHandle) {
     let env_filter =
         EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(env_filter));
+    let (env_filter, handle) = reload::Layer::new(env_filter);
 
     let formatting_layer = BunyanFormattingLayer::new(name, std::io::stdout);
 
-    Registry::default()
+    let subscriber = Registry::default()
         .with(env_filter)
         .with(JsonStorageLayer)
-        .with(formatting_layer)
+        .with(formatting_layer);
+
+    (subscriber, handle)
 }
 
 pub fn init_subscriber(
@@ -24,3 +35,12 @@ pub fn init_subscriber(
     LogTracer::init().expect("failed to set logger");
     set_global_default(subscriber)
 }
+
+pub fn set_log_level(handle: &LogFilterHandle, log_level: &str) -> Result<(), Error> {
+    let env_filter = EnvFilter::try_new(log_level)
+        .map_err(|_| Error::InvalidSettings(format!("invalid log level `{}`", log_level)))?;
+
+    handle
+        .reload(env_filter)
+        .map_err(|e| Error::InvalidSettings(format!("failed to change the log level: {}", e)))
+}